@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE vocab_progresses;
+DELETE FROM users WHERE id = '00000000-0000-0000-0000-000000000000';
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE vocab_progresses (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY NOT NULL,
+  user_id UUID NOT NULL REFERENCES users(id),
+  vocab VARCHAR NOT NULL REFERENCES vocabs,
+  ease_factor DOUBLE PRECISION NOT NULL DEFAULT 2.5,
+  interval_days INTEGER NOT NULL DEFAULT 0,
+  repetitions INTEGER NOT NULL DEFAULT 0,
+  lapses INTEGER NOT NULL DEFAULT 0,
+  due_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  reviewed_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  UNIQUE (user_id, vocab)
+);
+COMMENT ON TABLE vocab_progresses IS '每个用户每个单词的SM-2复习进度';
+CREATE INDEX vocab_progresses_user_due_idx ON vocab_progresses (user_id, due_at);
+SELECT diesel_manage_updated_at('vocab_progresses');
+
+-- 未登录时使用的访客账号
+INSERT INTO users (id, username, email)
+VALUES ('00000000-0000-0000-0000-000000000000', 'guest', '');
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE review_logs (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY NOT NULL,
+  user_id UUID NOT NULL REFERENCES users(id),
+  vocab VARCHAR NOT NULL REFERENCES vocabs,
+  action VARCHAR NOT NULL,
+  latency_ms INTEGER NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
+COMMENT ON TABLE review_logs IS '用户每次点击 知道/不知道/正确/记错了 的记录';
+COMMENT ON COLUMN review_logs.action IS 'know, dontknow, right, wrong';
+COMMENT ON COLUMN review_logs.latency_ms IS '从显示卡片到点击按钮的毫秒数';
+CREATE INDEX review_logs_user_created_idx ON review_logs (user_id, created_at);
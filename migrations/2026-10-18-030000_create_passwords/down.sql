@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_email_idx;
+DROP INDEX users_username_idx;
+INSERT INTO users (id, username, email)
+VALUES ('00000000-0000-0000-0000-000000000000', 'guest', '');
+DROP TABLE passwords;
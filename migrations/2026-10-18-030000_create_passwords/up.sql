@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+CREATE TABLE passwords (
+  user_id UUID PRIMARY KEY NOT NULL REFERENCES users(id),
+  passwd VARCHAR NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
+COMMENT ON COLUMN passwords.passwd IS 'crypt(明文, gen_salt(''bf''))';
+SELECT diesel_manage_updated_at('passwords');
+
+-- 有了真正的账号, 不再需要访客
+DELETE FROM review_logs WHERE user_id = '00000000-0000-0000-0000-000000000000';
+DELETE FROM vocab_progresses WHERE user_id = '00000000-0000-0000-0000-000000000000';
+DELETE FROM users WHERE id = '00000000-0000-0000-0000-000000000000';
+
+CREATE UNIQUE INDEX users_username_idx ON users (username);
+CREATE UNIQUE INDEX users_email_idx ON users (lower(email));
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER set_updated_at ON vocab_books;
+DROP INDEX vocab_books_created_by_idx;
+DROP INDEX vocab_book_contents_book_vocab_idx;
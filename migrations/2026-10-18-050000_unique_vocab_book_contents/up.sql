@@ -0,0 +1,4 @@
+-- Your SQL goes here
+CREATE UNIQUE INDEX vocab_book_contents_book_vocab_idx ON vocab_book_contents (book_id, vocab);
+CREATE INDEX vocab_books_created_by_idx ON vocab_books (created_by);
+SELECT diesel_manage_updated_at('vocab_books');
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX vocab_dicts_sense_idx;
+ALTER TABLE users DROP COLUMN is_admin;
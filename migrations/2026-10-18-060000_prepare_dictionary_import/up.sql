@@ -0,0 +1,10 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- 导入时按 (单词, 词性, 释义) 去重
+DELETE FROM vocab_dicts a USING vocab_dicts b
+WHERE a.id > b.id
+  AND a.vocab = b.vocab
+  AND a.partofspeech = b.partofspeech
+  AND a.meaning = b.meaning;
+CREATE UNIQUE INDEX vocab_dicts_sense_idx ON vocab_dicts (vocab, partofspeech, meaning);
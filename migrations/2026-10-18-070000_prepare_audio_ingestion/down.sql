@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER set_updated_at ON vocab_speeches;
+ALTER TABLE vocab_speeches DROP COLUMN format;
+DROP INDEX vocab_speeches_vocab_idx;
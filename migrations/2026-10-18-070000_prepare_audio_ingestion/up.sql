@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- 每个单词只保留最新的一条发音
+DELETE FROM vocab_speeches a USING vocab_speeches b
+WHERE a.vocab = b.vocab
+  AND (a.updated_at, a.id) < (b.updated_at, b.id);
+CREATE UNIQUE INDEX vocab_speeches_vocab_idx ON vocab_speeches (vocab);
+
+ALTER TABLE vocab_speeches ADD COLUMN format VARCHAR NOT NULL DEFAULT 'mp3';
+COMMENT ON COLUMN vocab_speeches.format IS '音频格式, mp3 或 ogg, 数据都存在 mp3 列';
+SELECT diesel_manage_updated_at('vocab_speeches');
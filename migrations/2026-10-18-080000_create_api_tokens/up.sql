@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE api_tokens (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY NOT NULL,
+  user_id UUID NOT NULL REFERENCES users(id),
+  name VARCHAR NOT NULL,
+  token_hash BYTEA NOT NULL UNIQUE,
+  last_used_at TIMESTAMP WITH TIME ZONE,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
+COMMENT ON TABLE api_tokens IS '手机等客户端访问 /api/v1 用的令牌';
+COMMENT ON COLUMN api_tokens.token_hash IS '令牌的 sha256, 令牌本身只在创建时返回一次';
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
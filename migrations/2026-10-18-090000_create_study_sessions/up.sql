@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE study_sessions (
+  user_id UUID PRIMARY KEY NOT NULL REFERENCES users(id),
+  state VARCHAR NOT NULL DEFAULT 'to_answer',
+  vocab VARCHAR REFERENCES vocabs,
+  book_id UUID REFERENCES vocab_books(id) ON DELETE SET NULL,
+  shown_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
+COMMENT ON TABLE study_sessions IS '每个用户正在背的卡片, 换浏览器或设备也能接着背';
+COMMENT ON COLUMN study_sessions.state IS 'to_answer, to_check, to_remember';
+COMMENT ON COLUMN study_sessions.book_id IS '正在背的单词本, 为空时背全部单词';
+COMMENT ON COLUMN study_sessions.shown_at IS '卡片显示的时间, 用来计算 review_logs.latency_ms';
+SELECT diesel_manage_updated_at('study_sessions');
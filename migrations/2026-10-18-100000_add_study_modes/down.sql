@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE study_sessions
+  DROP COLUMN answer,
+  DROP COLUMN mode;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE study_sessions
+  ADD COLUMN mode VARCHAR NOT NULL DEFAULT 'flashcard',
+  ADD COLUMN answer VARCHAR;
+COMMENT ON COLUMN study_sessions.mode IS 'flashcard 自己判断, typing 看释义拼写单词';
+COMMENT ON COLUMN study_sessions.answer IS '拼写模式下输入的答案, 打分后显示差异';
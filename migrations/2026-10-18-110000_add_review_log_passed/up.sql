@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE review_logs ADD COLUMN passed BOOLEAN;
+COMMENT ON COLUMN review_logs.passed IS '这次点击结束了卡片时是否答对, 知道/不知道 这种只是翻开释义的点击为空';
+-- 之前的记录里 正确/错误 这两种点击结束了卡片
+UPDATE review_logs SET passed = (action = 'right') WHERE action IN ('right', 'wrong');
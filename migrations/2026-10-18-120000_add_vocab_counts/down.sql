@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX vocabs_created_at_idx;
+ALTER TABLE vocabs
+  DROP COLUMN like_count,
+  DROP COLUMN review_count;
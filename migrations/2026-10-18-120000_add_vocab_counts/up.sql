@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- 单词列表按复习次数和收藏数排序, 不用每次都去数
+ALTER TABLE vocabs
+  ADD COLUMN review_count INTEGER NOT NULL DEFAULT 0,
+  ADD COLUMN like_count INTEGER NOT NULL DEFAULT 0;
+COMMENT ON COLUMN vocabs.review_count IS '所有用户复习完这个单词的次数, 即 review_logs 里 right 和 wrong 的条数';
+COMMENT ON COLUMN vocabs.like_count IS '收藏这个单词的用户数';
+UPDATE vocabs SET review_count = (
+  SELECT COUNT(*) FROM review_logs
+  WHERE review_logs.vocab = vocabs.vocab AND review_logs.action IN ('right', 'wrong')
+);
+CREATE INDEX vocabs_review_count_idx ON vocabs (review_count DESC);
+CREATE INDEX vocabs_like_count_idx ON vocabs (like_count DESC);
+CREATE INDEX vocabs_created_at_idx ON vocabs (created_at DESC);
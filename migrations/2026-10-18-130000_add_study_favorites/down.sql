@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE vocab_likes;
+ALTER TABLE study_sessions DROP COLUMN favorites;
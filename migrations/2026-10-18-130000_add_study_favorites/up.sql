@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE study_sessions ADD COLUMN favorites BOOLEAN NOT NULL DEFAULT FALSE;
+COMMENT ON COLUMN study_sessions.favorites IS '是否在学习收藏的单词, 这时 book_id 为空';
+
+CREATE TABLE vocab_likes (
+  user_id UUID NOT NULL REFERENCES users(id),
+  vocab VARCHAR NOT NULL REFERENCES vocabs,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  PRIMARY KEY (user_id, vocab)
+);
+COMMENT ON TABLE vocab_likes IS '用户收藏的单词';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX vocab_dicts_meaning_trgm_idx;
+DROP INDEX vocab_dicts_meaning_tsv_idx;
+DROP INDEX vocabs_vocab_trgm_idx;
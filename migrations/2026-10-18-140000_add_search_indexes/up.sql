@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- 按前缀查单词, 不分大小写
+CREATE INDEX vocabs_vocab_trgm_idx ON vocabs USING GIN (vocab gin_trgm_ops);
+-- 按释义查: 英文释义用全文检索, 中文等没有空格分词的用三元组匹配
+CREATE INDEX vocab_dicts_meaning_tsv_idx ON vocab_dicts USING GIN (to_tsvector('simple', meaning));
+CREATE INDEX vocab_dicts_meaning_trgm_idx ON vocab_dicts USING GIN (meaning gin_trgm_ops);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE vocab_examples (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY NOT NULL,
+  dict_id UUID NOT NULL REFERENCES vocab_dicts(id) ON DELETE CASCADE,
+  body TEXT NOT NULL,
+  created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
+COMMENT ON TABLE vocab_examples IS '每个释义的例句和用法说明';
+COMMENT ON COLUMN vocab_examples.body IS 'Markdown, 显示前经过 ammonia 清理';
+COMMENT ON COLUMN vocab_examples.created_by IS '写例句的用户, 导入的例句为空';
+CREATE INDEX vocab_examples_dict_idx ON vocab_examples (dict_id);
+-- 重复导入同一个文件不会重复添加例句
+CREATE UNIQUE INDEX vocab_examples_dict_body_idx ON vocab_examples (dict_id, md5(body));
+SELECT diesel_manage_updated_at('vocab_examples');
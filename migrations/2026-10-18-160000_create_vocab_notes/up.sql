@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE vocab_notes (
+  user_id UUID NOT NULL REFERENCES users(id),
+  vocab VARCHAR NOT NULL REFERENCES vocabs,
+  body TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+  PRIMARY KEY (user_id, vocab)
+);
+COMMENT ON TABLE vocab_notes IS '用户自己给单词写的笔记和记忆方法';
+COMMENT ON COLUMN vocab_notes.body IS 'Markdown, 显示前经过 ammonia 清理';
+SELECT diesel_manage_updated_at('vocab_notes');
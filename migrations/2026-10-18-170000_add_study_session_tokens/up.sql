@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE study_sessions ADD COLUMN token UUID NOT NULL DEFAULT uuid_generate_v4();
+COMMENT ON COLUMN study_sessions.token IS '卡片或状态一变就换, 学习页面的表单提交它而不是单词, 免得在源代码里看到答案';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE study_sessions
+  DROP COLUMN choice_options,
+  DROP COLUMN choice_by_word,
+  DROP COLUMN choice_sense;
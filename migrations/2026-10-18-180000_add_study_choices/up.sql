@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE study_sessions
+  ADD COLUMN choice_sense UUID REFERENCES vocab_dicts(id) ON DELETE SET NULL,
+  ADD COLUMN choice_by_word BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN choice_options VARCHAR[];
+COMMENT ON COLUMN study_sessions.choice_sense IS '选择题问的释义, 换卡片时清空, 刷新页面还是同一道题';
+COMMENT ON COLUMN study_sessions.choice_by_word IS '看单词选释义, 否则看释义选单词';
+COMMENT ON COLUMN study_sessions.choice_options IS '给出的选项, 只有其中的答案才算数';
@@ -0,0 +1,359 @@
+use rocket::{
+    http::{Cookie, Cookies, Status},
+    request::{self, FlashMessage, Form, FromRequest, Request},
+    response::{Flash, Redirect},
+    Outcome, State,
+};
+
+use chrono::Utc;
+use maud::{html, Markup};
+use uuid::Uuid;
+
+use crate::{db, db::*, flash_text, notification_view, page_view, TEXT};
+use crate::api::{self, ApiError};
+use crate::mailer::{Mailer, TokenPurpose};
+use crate::types::ServerAcceptLangauge;
+
+use anyhow::Result;
+
+// private cookie, encrypted with the configured secret_key
+const SESSION_COOKIE: &str = "user_id";
+const MIN_PASSWORD_LEN: usize = 8;
+
+impl<'a, 'r> FromRequest<'a, 'r> for User {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let conn = request.guard::<db::Connection>()?;
+        let user_id = request
+            .cookies()
+            .get_private(SESSION_COOKIE)
+            .and_then(|c| Uuid::parse_str(c.value()).ok());
+        match user_id.map(|id| User::get(&id, &conn)) {
+            Some(Ok(user)) => Outcome::Success(user),
+            Some(Err(diesel::result::Error::NotFound)) | None => {
+                Outcome::Failure((Status::Unauthorized, ()))
+            }
+            Some(Err(_)) => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// A signed in user with `is_admin` set, anyone else gets a 403.
+pub struct Admin(pub User);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let user = request.guard::<User>()?;
+        if user.is_admin {
+            Outcome::Success(Admin(user))
+        } else {
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+fn start_session(cookies: &mut Cookies, user: &User) {
+    cookies.add_private(Cookie::new(SESSION_COOKIE, user.id.to_string()));
+}
+
+#[derive(Responder)]
+pub enum Unauthorized {
+    Page(Redirect),
+    Api(ApiError),
+}
+
+/// Pages send the visitor to log in, the api answers in json.
+#[catch(401)]
+pub fn unauthorized(request: &Request) -> Unauthorized {
+    if request.uri().path().starts_with(api::BASE) {
+        Unauthorized::Api(ApiError(Status::Unauthorized, "missing or invalid token"))
+    } else {
+        Unauthorized::Page(Redirect::to(uri!(login_page)))
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct SignupForm {
+    username: String,
+    email: String,
+    password: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct LoginForm {
+    email: String,
+    password: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct ForgotForm {
+    email: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct ResetForm {
+    token: String,
+    password: String,
+}
+
+#[get("/signup")]
+pub fn signup_page(lang: ServerAcceptLangauge, flash: Option<FlashMessage>) -> Markup {
+    let flash_msg = flash_text(flash);
+    signup_view(lang, &flash_msg)
+}
+
+#[post("/signup", data = "<form>")]
+pub fn signup(
+    lang: ServerAcceptLangauge,
+    mut cookies: Cookies,
+    form: Form<SignupForm>,
+    mailer: State<Mailer>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    let username = form.username.trim();
+    let email = form.email.trim();
+    if username.is_empty() || !email.contains('@') || form.password.chars().count() < MIN_PASSWORD_LEN {
+        return Ok(Flash::warning(Redirect::to(uri!(signup_page)), TEXT[&lang]["signup-invalid"]));
+    }
+    if User::get_by_username(username, &conn)?.is_some() || User::get_by_email(email, &conn)?.is_some() {
+        return Ok(Flash::warning(Redirect::to(uri!(signup_page)), TEXT[&lang]["signup-taken"]));
+    }
+
+    let user = User::insert_with_password(username, email, &form.password, &conn)?;
+    start_session(&mut cookies, &user);
+    if let Err(e) = mailer.send_verification(&user, lang) {
+        log::error!("failed to send verification mail to {}: {:#}", user.email, e);
+        return Ok(Flash::warning(Redirect::to(uri!(crate::index)), TEXT[&lang]["mail-failed"]));
+    }
+    Ok(Flash::success(Redirect::to(uri!(crate::index)), TEXT[&lang]["signup-done"]))
+}
+
+#[get("/login")]
+pub fn login_page(lang: ServerAcceptLangauge, flash: Option<FlashMessage>) -> Markup {
+    let flash_msg = flash_text(flash);
+    login_view(lang, &flash_msg)
+}
+
+#[post("/login", data = "<form>")]
+pub fn login(
+    lang: ServerAcceptLangauge,
+    mut cookies: Cookies,
+    form: Form<LoginForm>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    let user = match User::get_by_email(form.email.trim(), &conn)? {
+        Some(user) if Password::get_match(&user.id, &form.password, &conn)?.is_some() => user,
+        _ => {
+            return Ok(Flash::warning(Redirect::to(uri!(login_page)), TEXT[&lang]["login-failed"]));
+        }
+    };
+    start_session(&mut cookies, &user);
+    Ok(Flash::success(Redirect::to(uri!(crate::index)), TEXT[&lang]["login-done"]))
+}
+
+#[get("/verify?<token>")]
+pub fn verify_email(
+    lang: ServerAcceptLangauge,
+    token: String,
+    mailer: State<Mailer>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    match mailer.verify_token(&token, TokenPurpose::VerifyEmail, Utc::now()) {
+        Some((user_id, _)) => {
+            User::mark_email_verified(&user_id, &conn)?;
+            Ok(Flash::success(Redirect::to(uri!(crate::index)), TEXT[&lang]["email-verified"]))
+        }
+        None => Ok(Flash::warning(Redirect::to(uri!(login_page)), TEXT[&lang]["token-invalid"])),
+    }
+}
+
+#[post("/verify/resend")]
+pub fn resend_verification(
+    lang: ServerAcceptLangauge,
+    user: User,
+    mailer: State<Mailer>,
+) -> Flash<Redirect> {
+    match mailer.send_verification(&user, lang) {
+        Ok(()) => Flash::success(Redirect::to(uri!(crate::index)), TEXT[&lang]["verify-sent"]),
+        Err(e) => {
+            log::error!("failed to send verification mail to {}: {:#}", user.email, e);
+            Flash::warning(Redirect::to(uri!(crate::index)), TEXT[&lang]["mail-failed"])
+        }
+    }
+}
+
+#[get("/forgot")]
+pub fn forgot_page(lang: ServerAcceptLangauge, flash: Option<FlashMessage>) -> Markup {
+    let flash_msg = flash_text(flash);
+    forgot_view(lang, &flash_msg)
+}
+
+#[post("/forgot", data = "<form>")]
+pub fn forgot(
+    lang: ServerAcceptLangauge,
+    form: Form<ForgotForm>,
+    mailer: State<Mailer>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    if let Some(user) = User::get_by_email(form.email.trim(), &conn)? {
+        let stamp = Password::get(&user.id, &conn)?.updated_at.timestamp_millis();
+        if let Err(e) = mailer.send_password_reset(&user, stamp, lang) {
+            log::error!("failed to send password reset mail to {}: {:#}", user.email, e);
+            return Ok(Flash::warning(Redirect::to(uri!(forgot_page)), TEXT[&lang]["mail-failed"]));
+        }
+    }
+    // same answer whether the account exists or not
+    Ok(Flash::success(Redirect::to(uri!(login_page)), TEXT[&lang]["forgot-sent"]))
+}
+
+#[get("/reset?<token>")]
+pub fn reset_page(lang: ServerAcceptLangauge, flash: Option<FlashMessage>, token: String) -> Markup {
+    let flash_msg = flash_text(flash);
+    reset_view(lang, &flash_msg, &token)
+}
+
+#[post("/reset", data = "<form>")]
+pub fn reset(
+    lang: ServerAcceptLangauge,
+    form: Form<ResetForm>,
+    mailer: State<Mailer>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    let invalid = Flash::warning(Redirect::to(uri!(login_page)), TEXT[&lang]["token-invalid"]);
+    let (user_id, stamp) = match mailer.verify_token(&form.token, TokenPurpose::ResetPassword, Utc::now()) {
+        Some(claims) => claims,
+        None => return Ok(invalid),
+    };
+    // the password changed since the mail was sent, so the link was already used
+    if Password::get(&user_id, &conn)?.updated_at.timestamp_millis() != stamp {
+        return Ok(invalid);
+    }
+    if form.password.chars().count() < MIN_PASSWORD_LEN {
+        return Ok(Flash::warning(
+            Redirect::to(uri!(reset_page: form.token.as_str())),
+            TEXT[&lang]["signup-invalid"],
+        ));
+    }
+    Password::update(&user_id, &form.password, &conn)?;
+    Ok(Flash::success(Redirect::to(uri!(login_page)), TEXT[&lang]["reset-done"]))
+}
+
+#[post("/logout")]
+pub fn logout(mut cookies: Cookies) -> Redirect {
+    cookies.remove_private(Cookie::named(SESSION_COOKIE));
+    Redirect::to(uri!(login_page))
+}
+
+fn account_form_view(lang: ServerAcceptLangauge, flash_msg: &Option<String>, form: Markup) -> Markup {
+    page_view(
+        &lang,
+        html! {
+            section class="hero is-primary is-fullheight" {
+                div class="hero-body" {
+                    div class="container" {
+                        div class="columns is-centered" {
+                            div class="column is-half-tablet is-one-third-desktop is-one-quarter-widescreen" {
+                                (notification_view(flash_msg))
+                                div class="box" {
+                                    (form)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn text_field(label: &str, name: &str, kind: &str) -> Markup {
+    html! {
+        div class="field" {
+            label class="label" { (label) }
+            div class="control" {
+                input class="input" type=(kind) name=(name) required? {}
+            }
+        }
+    }
+}
+
+fn login_view(lang: ServerAcceptLangauge, flash_msg: &Option<String>) -> Markup {
+    let text = &TEXT[&lang];
+    account_form_view(
+        lang,
+        flash_msg,
+        html! {
+            form action=(uri!(login).to_string()) method="post" {
+                p class="title has-text-black" { (text["login-title"]) }
+                (text_field(text["email"], "email", "email"))
+                (text_field(text["password"], "password", "password"))
+                div class="field" {
+                    button class="button is-black is-fullwidth" type="submit" { (text["login-title"]) }
+                }
+            }
+            div class="level is-mobile" {
+                a class="level-left" href=(uri!(signup_page).to_string()) { (text["no-account"]) }
+                a class="level-right" href=(uri!(forgot_page).to_string()) { (text["forgot-link"]) }
+            }
+        },
+    )
+}
+
+fn forgot_view(lang: ServerAcceptLangauge, flash_msg: &Option<String>) -> Markup {
+    let text = &TEXT[&lang];
+    account_form_view(
+        lang,
+        flash_msg,
+        html! {
+            form action=(uri!(forgot).to_string()) method="post" {
+                p class="title has-text-black" { (text["forgot-title"]) }
+                (text_field(text["email"], "email", "email"))
+                div class="field" {
+                    button class="button is-black is-fullwidth" type="submit" { (text["forgot-title"]) }
+                }
+            }
+            a href=(uri!(login_page).to_string()) { (text["have-account"]) }
+        },
+    )
+}
+
+fn reset_view(lang: ServerAcceptLangauge, flash_msg: &Option<String>, token: &str) -> Markup {
+    let text = &TEXT[&lang];
+    account_form_view(
+        lang,
+        flash_msg,
+        html! {
+            form action=(uri!(reset).to_string()) method="post" {
+                p class="title has-text-black" { (text["reset-title"]) }
+                input type="hidden" name="token" value=(token) {}
+                (text_field(text["password"], "password", "password"))
+                div class="field" {
+                    button class="button is-black is-fullwidth" type="submit" { (text["reset-title"]) }
+                }
+            }
+        },
+    )
+}
+
+fn signup_view(lang: ServerAcceptLangauge, flash_msg: &Option<String>) -> Markup {
+    let text = &TEXT[&lang];
+    account_form_view(
+        lang,
+        flash_msg,
+        html! {
+            form action=(uri!(signup).to_string()) method="post" {
+                p class="title has-text-black" { (text["signup-title"]) }
+                (text_field(text["username"], "username", "text"))
+                (text_field(text["email"], "email", "email"))
+                (text_field(text["password"], "password", "password"))
+                div class="field" {
+                    button class="button is-black is-fullwidth" type="submit" { (text["signup-title"]) }
+                }
+            }
+            a href=(uri!(login_page).to_string()) { (text["have-account"]) }
+        },
+    )
+}
@@ -0,0 +1,634 @@
+// Anki decks in and out of vocab books, see https://docs.ankiweb.net/importing/text-files.html
+// and the legacy .apkg layout: a zip of `collection.anki2` (sqlite), a `media` json map and the media files.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use rocket::{
+    data::Data,
+    http::{ContentType, Status},
+    request::FlashMessage,
+    Response,
+};
+use rocket_contrib::{
+    databases::diesel::{Connection, OptionalExtension, PgConnection},
+    uuid::Uuid as UuidParam,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use maud::{html, Markup};
+use rusqlite::{params, NO_PARAMS};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    account::Admin,
+    app_page_view, audio, db, db::*, flash_text,
+    import::{self, import_report_view, read_limited, ImportReport, ImportRow, RowError, RowProblem},
+    types::ServerAcceptLangauge,
+    TEXT,
+};
+
+// decks carry their media, so they may be bigger than dictionary files
+const MAX_DECK_BYTES: u64 = 64 * 1024 * 1024;
+// what a deck may unpack to, a zip entry can be far bigger than what it takes in the zip
+pub const MAX_UNPACKED_BYTES: u64 = 256 * 1024 * 1024;
+// a sense whose part of speech we cannot tell
+const UNKNOWN_PARTOFSPEECH: &str = "-";
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// A note of a deck, `word` and `meaning` are plain text, one sense per line of `meaning`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnkiNote {
+    pub deck: String,
+    pub word: String,
+    pub meaning: String,
+    pub audio: Option<Vec<u8>>,
+}
+
+impl AnkiNote {
+    fn from_fields(deck: &str, fields: &[&str], audio: Option<Vec<u8>>) -> AnkiNote {
+        let mut fields = fields.iter().map(|f| plain_text(f));
+        AnkiNote {
+            deck: deck.to_string(),
+            // the first line only, some decks put the phonetics under the word
+            word: fields
+                .next()
+                .unwrap_or_default()
+                .lines()
+                .next()
+                .unwrap_or("")
+                .to_string(),
+            meaning: fields.find(|f| !f.is_empty()).unwrap_or_default(),
+            audio,
+        }
+    }
+
+    /// One row per sense, the deck becomes the book.
+    pub fn to_rows(&self) -> Vec<ImportRow> {
+        let senses: Vec<&str> = self.meaning.lines().collect();
+        let senses = if senses.is_empty() { vec![""] } else { senses };
+        senses
+            .into_iter()
+            .map(|sense| {
+                let (partofspeech, meaning) = split_partofspeech(sense);
+                ImportRow {
+                    word: self.word.clone(),
+                    partofspeech: partofspeech.to_string(),
+                    meaning: meaning.to_string(),
+                    book: Some(self.deck.clone()),
+                    example: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// "n. 欢呼声" and "名词 欢呼声" both start with a part of speech, "欢呼声" does not.
+fn split_partofspeech(sense: &str) -> (&str, &str) {
+    let sense = sense.trim();
+    if let Some((first, rest)) = sense.split_once(char::is_whitespace) {
+        let abbreviation = first.len() <= 6
+            && first.ends_with('.')
+            && first.trim_end_matches('.').chars().all(|c| c.is_ascii_alphabetic() || c == '.');
+        let chinese = first.chars().count() <= 4 && first.ends_with('词');
+        if (abbreviation || chinese) && !rest.trim().is_empty() {
+            return (first, rest.trim());
+        }
+    }
+    (UNKNOWN_PARTOFSPEECH, sense)
+}
+
+/// Drops tags and `[sound:...]`, block tags and `<br>` become line breaks.
+fn plain_text(field: &str) -> String {
+    let mut text = String::new();
+    let mut tag: Option<String> = None;
+    for c in field.chars() {
+        if let Some(name) = tag.as_mut() {
+            if c != '>' {
+                name.push(c);
+                continue;
+            }
+            let name = name
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or("")
+                .to_lowercase();
+            if matches!(name.as_str(), "br" | "div" | "p" | "li") {
+                text.push('\n');
+            }
+            tag = None;
+        } else if c == '<' {
+            tag = Some(String::new());
+        } else {
+            text.push(c);
+        }
+    }
+    let mut text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    while let Some(start) = text.find("[sound:") {
+        match text[start..].find(']') {
+            Some(end) => text.replace_range(start..start + end + 1, ""),
+            None => break,
+        }
+    }
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn sound_files(field: &str) -> impl Iterator<Item = &str> {
+    field
+        .split("[sound:")
+        .skip(1)
+        .filter_map(|rest| rest.split(']').next())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The collection inside a deck has to be a file for sqlite.
+fn with_collection_file<T>(f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let path: PathBuf = std::env::temp_dir().join(format!("beidanci-{}.anki2", Uuid::new_v4()));
+    let result = f(&path);
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// `budget` is what is left to unpack of the whole deck.
+fn read_entry<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    budget: &mut u64,
+) -> Result<Option<Vec<u8>>> {
+    match archive.by_name(name) {
+        Ok(file) => {
+            let bytes = read_limited(file, *budget)?.context(RowProblem::DeckTooBig)?;
+            *budget -= bytes.len() as u64;
+            Ok(Some(bytes))
+        }
+        Err(zip::result::ZipError::FileNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Notes of an .apkg with their position in the collection, from 1.
+pub fn read_apkg(bytes: &[u8]) -> Result<Vec<(u64, AnkiNote)>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).context(RowProblem::NotApkg)?;
+    let mut budget = MAX_UNPACKED_BYTES;
+    // newer Anki writes both, the anki2 one only says to upgrade
+    let collection = match read_entry(&mut archive, "collection.anki21", &mut budget)? {
+        Some(collection) => collection,
+        None if archive.file_names().any(|name| name == "collection.anki21b") => {
+            return Err(anyhow!(RowProblem::DeckNeedsNewerAnki));
+        }
+        None => read_entry(&mut archive, "collection.anki2", &mut budget)?.context(RowProblem::DeckWithoutCollection)?,
+    };
+    // {"0": "cheer.mp3"}, the file is stored in the zip as "0"
+    let media: HashMap<String, String> = read_entry(&mut archive, "media", &mut budget)?
+        .and_then(|media| serde_json::from_slice(&media).ok())
+        .unwrap_or_default();
+    let media: HashMap<String, String> = media.into_iter().map(|(entry, name)| (name, entry)).collect();
+
+    let notes = with_collection_file(|path| {
+        fs::write(path, &collection)?;
+        let sqlite = rusqlite::Connection::open(path)?;
+        let decks: String = sqlite.query_row("SELECT decks FROM col", NO_PARAMS, |row| row.get(0))?;
+        let decks: HashMap<String, serde_json::Value> = serde_json::from_str(&decks)?;
+        let mut statement = sqlite.prepare(
+            "SELECT notes.id, notes.flds, cards.did FROM notes JOIN cards ON cards.nid = notes.id
+             ORDER BY notes.id, cards.ord",
+        )?;
+        let rows = statement.query_map(NO_PARAMS, |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+        })?;
+        let mut notes: Vec<(String, String)> = Vec::new();
+        let mut last_note = None;
+        for row in rows {
+            let (note_id, fields, deck_id) = row?;
+            // a note with several cards is still one word, it goes to the deck of its first card
+            if last_note == Some(note_id) {
+                continue;
+            }
+            last_note = Some(note_id);
+            let deck = decks
+                .get(&deck_id.to_string())
+                .and_then(|deck| deck["name"].as_str())
+                .unwrap_or("Default");
+            notes.push((deck.to_string(), fields));
+        }
+        Ok(notes)
+    })?;
+
+    let mut result = Vec::with_capacity(notes.len());
+    for (i, (deck, fields)) in notes.into_iter().enumerate() {
+        let fields: Vec<&str> = fields.split(FIELD_SEPARATOR).collect();
+        let audio = match fields
+            .iter()
+            .flat_map(|f| sound_files(f))
+            .find(|name| name.to_lowercase().ends_with(".mp3"))
+            .and_then(|name| media.get(name))
+        {
+            Some(entry) => read_entry(&mut archive, entry, &mut budget)?,
+            None => None,
+        };
+        result.push((i as u64 + 1, AnkiNote::from_fields(&deck, &fields, audio)));
+    }
+    Ok(result)
+}
+
+/// Notes of an Anki "Notes in Plain Text" export, by line. The `#deck:` header or
+/// column names the deck, otherwise every note goes to `default_deck`.
+pub fn read_text(input: &str, default_deck: &str) -> (Vec<(u64, AnkiNote)>, Vec<RowError>) {
+    let mut delimiter = b'\t';
+    let mut deck = default_deck.to_string();
+    // 1 based columns that are not fields of the note
+    let mut deck_column = None;
+    let mut skip_columns = Vec::new();
+    let mut header_lines = 0;
+    for line in input.lines().take_while(|line| line.starts_with('#')) {
+        header_lines += 1;
+        let (key, value) = line[1..].split_once(':').unwrap_or((&line[1..], ""));
+        let value = value.trim();
+        match key.trim() {
+            "separator" => {
+                delimiter = match value {
+                    "tab" | "Tab" => b'\t',
+                    "comma" | "Comma" => b',',
+                    "semicolon" | "Semicolon" => b';',
+                    "space" | "Space" => b' ',
+                    "pipe" | "Pipe" => b'|',
+                    other => other.bytes().next().unwrap_or(b'\t'),
+                }
+            }
+            "deck" => deck = value.to_string(),
+            "deck column" => deck_column = value.parse::<usize>().ok(),
+            "notetype column" | "guid column" | "tags column" => skip_columns.extend(value.parse::<usize>().ok()),
+            _ => {}
+        }
+    }
+    skip_columns.extend(deck_column);
+
+    let body: String = input
+        .lines()
+        .skip(header_lines)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let mut notes = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        match record {
+            Ok(record) => {
+                let line = header_lines as u64 + record.position().map_or(0, |p| p.line());
+                let note_deck = deck_column
+                    .and_then(|column| record.get(column - 1))
+                    .filter(|d| !d.trim().is_empty())
+                    .unwrap_or(&deck);
+                let fields: Vec<&str> = record
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !skip_columns.contains(&(i + 1)))
+                    .map(|(_, field)| field)
+                    .collect();
+                notes.push((line, AnkiNote::from_fields(note_deck.trim(), &fields, None)));
+            }
+            Err(e) => errors.push(RowError {
+                line: header_lines as u64 + e.position().map_or(0, |p| p.line()),
+                problem: RowProblem::Unreadable(e.to_string()),
+            }),
+        }
+    }
+    (notes, errors)
+}
+
+const COLLECTION_SCHEMA: &str = "
+CREATE TABLE col (id integer PRIMARY KEY, crt integer NOT NULL, mod integer NOT NULL, scm integer NOT NULL,
+    ver integer NOT NULL, dty integer NOT NULL, usn integer NOT NULL, ls integer NOT NULL, conf text NOT NULL,
+    models text NOT NULL, decks text NOT NULL, dconf text NOT NULL, tags text NOT NULL);
+CREATE TABLE notes (id integer PRIMARY KEY, guid text NOT NULL, mid integer NOT NULL, mod integer NOT NULL,
+    usn integer NOT NULL, tags text NOT NULL, flds text NOT NULL, sfld text NOT NULL, csum integer NOT NULL,
+    flags integer NOT NULL, data text NOT NULL);
+CREATE TABLE cards (id integer PRIMARY KEY, nid integer NOT NULL, did integer NOT NULL, ord integer NOT NULL,
+    mod integer NOT NULL, usn integer NOT NULL, type integer NOT NULL, queue integer NOT NULL, due integer NOT NULL,
+    ivl integer NOT NULL, factor integer NOT NULL, reps integer NOT NULL, lapses integer NOT NULL,
+    left integer NOT NULL, odue integer NOT NULL, odid integer NOT NULL, flags integer NOT NULL, data text NOT NULL);
+CREATE TABLE revlog (id integer PRIMARY KEY, cid integer NOT NULL, usn integer NOT NULL, ease integer NOT NULL,
+    ivl integer NOT NULL, lastIvl integer NOT NULL, factor integer NOT NULL, time integer NOT NULL,
+    type integer NOT NULL);
+CREATE TABLE graves (usn integer NOT NULL, oid integer NOT NULL, type integer NOT NULL);
+";
+
+// Anki's duplicate check, the first 8 hex digits of sha1 of the sort field
+fn field_checksum(field: &str) -> i64 {
+    let digest = Sha1::digest(plain_text(field).as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as i64
+}
+
+fn media_name(i: usize, word: &str) -> String {
+    let word: String = word
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("beidanci_{}_{}.mp3", i, word)
+}
+
+/// A legacy .apkg with one "Front/Back" note per note, the word plays its audio on the front.
+pub fn write_apkg(notes: &[AnkiNote], now: DateTime<Utc>) -> Result<Vec<u8>> {
+    let millis = now.timestamp_millis();
+    let seconds = now.timestamp();
+    let model_id = millis;
+    let mut deck_ids: BTreeMap<&str, i64> = BTreeMap::new();
+    for note in notes {
+        let next_id = millis + 1 + deck_ids.len() as i64;
+        deck_ids.entry(note.deck.as_str()).or_insert(next_id);
+    }
+
+    let mut decks = serde_json::Map::new();
+    for (name, id) in std::iter::once(("Default", 1)).chain(deck_ids.iter().map(|(n, id)| (*n, *id))) {
+        decks.insert(
+            id.to_string(),
+            json!({
+                "id": id, "name": name, "mod": seconds, "usn": -1, "desc": "", "dyn": 0, "conf": 1,
+                "collapsed": false, "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0],
+                "timeToday": [0, 0], "extendNew": 10, "extendRev": 50,
+            }),
+        );
+    }
+    let field = |name: &str, ord: i32| {
+        json!({"name": name, "ord": ord, "sticky": false, "rtl": false, "font": "Arial", "size": 20, "media": []})
+    };
+    let models = json!({
+        model_id.to_string(): {
+            "id": model_id, "name": "beidanci", "type": 0, "mod": seconds, "usn": -1, "sortf": 0,
+            "did": deck_ids.values().next().copied().unwrap_or(1),
+            "tmpls": [{
+                "name": "Card 1", "ord": 0, "qfmt": "{{Front}}",
+                "afmt": "{{FrontSide}}<hr id=answer>{{Back}}", "did": null, "bqfmt": "", "bafmt": "",
+            }],
+            "flds": [field("Front", 0), field("Back", 1)],
+            "css": ".card { font-family: arial; font-size: 20px; text-align: center; }",
+            "latexPre": "", "latexPost": "", "tags": [], "vers": [], "req": [[0, "all", [0]]],
+        }
+    });
+    let dconf = json!({
+        "1": {
+            "id": 1, "name": "Default", "mod": 0, "usn": 0, "maxTaken": 60, "autoplay": true, "timer": 0,
+            "replayq": true, "dyn": false,
+            "new": {"delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500, "order": 1, "perDay": 20,
+                    "bury": true, "separate": true},
+            "rev": {"perDay": 200, "ease4": 1.3, "fuzz": 0.05, "maxIvl": 36500, "ivlFct": 1, "bury": true,
+                    "minSpace": 1},
+            "lapse": {"delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0},
+        }
+    });
+    let conf = json!({"nextPos": notes.len() + 1, "curModel": model_id.to_string(), "activeDecks": [1], "curDeck": 1});
+
+    let mut media = BTreeMap::new();
+    let mut media_files = Vec::new();
+    let collection = with_collection_file(|path| {
+        let sqlite = rusqlite::Connection::open(path)?;
+        sqlite.execute_batch(COLLECTION_SCHEMA)?;
+        sqlite.execute(
+            "INSERT INTO col VALUES (1, ?1, ?2, ?3, 11, 0, 0, 0, ?4, ?5, ?6, ?7, '{}')",
+            params![
+                seconds,
+                millis,
+                millis,
+                conf.to_string(),
+                models.to_string(),
+                serde_json::Value::Object(decks).to_string(),
+                dconf.to_string()
+            ],
+        )?;
+        for (i, note) in notes.iter().enumerate() {
+            let mut front = escape_html(&note.word);
+            if let Some(audio) = &note.audio {
+                let name = media_name(i, &note.word);
+                front.push_str(&format!("[sound:{}]", name));
+                media.insert(media_files.len().to_string(), name);
+                media_files.push(audio);
+            }
+            let back = note
+                .meaning
+                .lines()
+                .map(escape_html)
+                .collect::<Vec<_>>()
+                .join("<br>");
+            let id = millis + i as i64;
+            sqlite.execute(
+                "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, '', ?5, ?6, ?7, 0, '')",
+                params![
+                    id,
+                    // the same word keeps its note when the deck is imported again
+                    format!("beidanci:{}", note.word),
+                    model_id,
+                    seconds,
+                    format!("{}{}{}", front, FIELD_SEPARATOR, back),
+                    note.word,
+                    field_checksum(&front)
+                ],
+            )?;
+            sqlite.execute(
+                "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                params![id, deck_ids[note.deck.as_str()], seconds, i as i64 + 1],
+            )?;
+        }
+        drop(sqlite);
+        Ok(fs::read(path)?)
+    })?;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("collection.anki2", FileOptions::default())?;
+    zip.write_all(&collection)?;
+    zip.start_file("media", FileOptions::default())?;
+    zip.write_all(serde_json::to_string(&media)?.as_bytes())?;
+    // mp3 does not get any smaller
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for (i, audio) in media_files.into_iter().enumerate() {
+        zip.start_file(i.to_string(), stored)?;
+        zip.write_all(audio)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Adds the notes to `owner`'s books named after their decks, with their audio for words that have none.
+pub fn import_notes(
+    notes: &[(u64, AnkiNote)],
+    mut failed: Vec<RowError>,
+    owner: &Uuid,
+    connection: &PgConnection,
+) -> Result<ImportReport> {
+    let mut rows = Vec::new();
+    for (line, note) in notes {
+        for row in note.to_rows() {
+            match row.validate() {
+                Ok(row) => rows.push((*line, row)),
+                Err(problem) => failed.push(RowError { line: *line, problem }),
+            }
+        }
+    }
+    connection.transaction(|| {
+        let mut report = import::import(&rows, Some(owner), connection)?;
+        for (line, note) in notes {
+            let imported = rows.iter().any(|(_, row)| row.word == note.word);
+            if let (true, Some(audio)) = (imported, &note.audio) {
+                // decks name their sounds .mp3 whatever they hold
+                let format = match audio::probe(audio) {
+                    Ok((format, _)) => format,
+                    Err(reason) => {
+                        report.failed.push(RowError { line: *line, problem: RowProblem::Audio(note.word.clone(), reason) });
+                        continue;
+                    }
+                };
+                let speech = NewVocabSpeech {
+                    vocab: &note.word,
+                    mp3: audio,
+                    format: format.into(),
+                };
+                if VocabSpeech::insert_if_missing(&speech, connection)? {
+                    report.new_audio += 1;
+                }
+            }
+        }
+        report.failed.extend(failed);
+        report.failed.sort_by_key(|e| e.line);
+        Ok(report)
+    })
+}
+
+fn book_notes(book: &VocabBook, connection: &PgConnection) -> Result<Vec<AnkiNote>> {
+    let mut notes = Vec::new();
+    for content in VocabBookContent::get_by_book(&book.id, connection)? {
+        let senses = VocabDict::group_senses(&VocabDict::get_by_word(&content.vocab, connection)?);
+        let meaning = senses
+            .iter()
+            .flat_map(|group| {
+                group.meanings.iter().map(move |meaning| match group.partofspeech.as_str() {
+                    UNKNOWN_PARTOFSPEECH => meaning.clone(),
+                    partofspeech => format!("{} {}", partofspeech, meaning),
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let audio = VocabSpeech::get_by_word(&content.vocab, connection)
+            .optional()?
+            .map(|speech| speech.mp3);
+        notes.push(AnkiNote {
+            deck: book.name.clone(),
+            word: content.vocab,
+            meaning,
+            audio,
+        });
+    }
+    Ok(notes)
+}
+
+// RFC 5987, book names are often not ascii
+fn attachment_header(file_name: &str) -> String {
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("attachment; filename=\"deck.apkg\"; filename*=UTF-8''{}", encoded)
+}
+
+/// Admins only, decks add their words and senses to the dictionary everyone studies.
+#[get("/books/import")]
+pub fn import_page(lang: ServerAcceptLangauge, admin: Admin, flash: Option<FlashMessage>) -> Markup {
+    let flash_msg = flash_text(flash);
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        &admin.0,
+        &flash_msg,
+        html! {
+            h1 class="title" { (text["anki-import-title"]) }
+            p class="block" { (text["anki-import-help"]) }
+            form id="anki-form" {
+                div class="field has-addons" {
+                    div class="control is-expanded" {
+                        input class="input" type="file" id="anki-file" accept=".apkg,.txt,.tsv,.csv" required? {}
+                    }
+                    div class="control" {
+                        button class="button is-primary" type="submit" { (text["import-submit"]) }
+                    }
+                }
+            }
+            (import::upload_js("anki-form", "anki-file", "'/books/import?name=' + encodeURIComponent(file.name)"))
+        },
+    )
+}
+
+/// `name` is the uploaded file's name, its extension tells .apkg from text and its stem names the deck.
+#[post("/books/import?<name>", data = "<data>")]
+pub fn import_upload(
+    lang: ServerAcceptLangauge,
+    admin: Admin,
+    name: String,
+    data: Data,
+    conn: db::Connection,
+) -> Result<Result<Markup, Status>> {
+    let bytes = match read_limited(data.open(), MAX_DECK_BYTES)? {
+        Some(bytes) => bytes,
+        None => return Ok(Err(Status::PayloadTooLarge)),
+    };
+
+    let path = Path::new(&name);
+    let (notes, failed) = if path.extension().map_or(false, |e| e.eq_ignore_ascii_case("apkg")) {
+        match read_apkg(&bytes) {
+            Ok(notes) => (notes, Vec::new()),
+            Err(e) => {
+                let problem = match e.downcast_ref::<RowProblem>() {
+                    Some(problem) => problem.clone(),
+                    None => RowProblem::Unreadable(format!("{:#}", e)),
+                };
+                (Vec::new(), vec![RowError { line: 0, problem }])
+            }
+        }
+    } else {
+        let deck = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Anki");
+        read_text(&String::from_utf8_lossy(&bytes), deck)
+    };
+    let report = import_notes(&notes, failed, &admin.0.id, &conn)?;
+    Ok(Ok(import_report_view(lang, &admin.0, "anki-import-title", &uri!(import_page).to_string(), &report)))
+}
+
+#[get("/books/<id>/export.apkg")]
+pub fn export_book(user: User, id: UuidParam, conn: db::Connection) -> Result<Option<Response<'static>>> {
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    let apkg = write_apkg(&book_notes(&book, &conn)?, Utc::now())?;
+    let response = Response::build()
+        .header(ContentType::new("application", "octet-stream"))
+        .raw_header("Content-Disposition", attachment_header(&format!("{}.apkg", book.name)))
+        .sized_body(Cursor::new(apkg))
+        .finalize();
+    Ok(Some(response))
+}
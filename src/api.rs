@@ -0,0 +1,320 @@
+// Versioned JSON API for mobile clients, mounted at `BASE`.
+// Clients trade email and password for a token once and send `Authorization: Bearer <token>` afterwards.
+use std::collections::HashMap;
+
+use rocket::{
+    http::Status,
+    request::{self, FromRequest, Request},
+    response::{self, status, Responder},
+    Outcome,
+};
+use rocket_contrib::{
+    databases::diesel::{PgConnection, QueryResult},
+    json::Json,
+    uuid::Uuid as UuidParam,
+};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    db, db::*,
+    scheduler::Grade,
+    stats::Progress,
+    types::{ReviewAction, ServerAcceptLangauge},
+    words, TEXT,
+};
+
+pub const BASE: &str = "/api/v1";
+const BEARER: &str = "Bearer ";
+
+/// `{"error": "..."}` with its status, mobile clients cannot read the html error pages.
+#[derive(Debug)]
+pub struct ApiError(pub Status, pub &'static str);
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        status::Custom(self.0, json!({ "error": self.1 })).respond_to(request)
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(e: diesel::result::Error) -> ApiError {
+        log::error!("api database error: {}", e);
+        ApiError(Status::InternalServerError, "internal error")
+    }
+}
+
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// The owner of the bearer token and which token it was.
+pub struct ApiUser {
+    pub user: User,
+    pub token_id: Uuid,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for ApiUser {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let conn = request.guard::<db::Connection>()?;
+        match ApiUser::authenticate(request.headers().get_one("Authorization"), &conn) {
+            Ok(Some(api)) => Outcome::Success(api),
+            Ok(None) => Outcome::Failure((Status::Unauthorized, ())),
+            Err(_) => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+impl ApiUser {
+    /// The owner of the token in an `Authorization` header, `None` without a valid one.
+    pub fn authenticate(authorization: Option<&str>, connection: &PgConnection) -> QueryResult<Option<ApiUser>> {
+        let token = match authorization.and_then(|value| value.strip_prefix(BEARER)) {
+            Some(token) => token.trim(),
+            None => return Ok(None),
+        };
+        Ok(ApiToken::authenticate(&token_hash(token), connection)?.map(|(token, user)| ApiUser {
+            user,
+            token_id: token.id,
+        }))
+    }
+}
+
+pub fn token_hash(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+fn new_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    email: String,
+    password: String,
+    /// shown to the user to tell their devices apart
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewToken {
+    id: Uuid,
+    token: String,
+}
+
+/// Where a card is in its schedule.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Learning {
+    /// never reviewed
+    New,
+    /// failed last time, coming back within the session
+    Relearning,
+    Review,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Card {
+    word: Vocab,
+    senses: Vec<VocabDict>,
+    /// `None` when nobody recorded the word
+    audio_url: Option<String>,
+    state: Learning,
+    progress: Option<VocabProgress>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    vocab: String,
+    /// "partofspeech meaning" of each sense
+    meanings: Vec<String>,
+    /// the page of the word on the site
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    query: String,
+    page: i64,
+    pages: i64,
+    words: Vec<SearchHit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GradeRequest {
+    vocab: String,
+    grade: Grade,
+    /// from showing the card to answering
+    latency_ms: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookSummary {
+    #[serde(flatten)]
+    book: VocabBook,
+    word_count: i64,
+}
+
+/// The counts the stats page shows, with the words studied and due now.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    /// words reviewed at least once
+    studied: i64,
+    due: i64,
+    #[serde(flatten)]
+    progress: Progress,
+}
+
+#[post("/tokens", format = "json", data = "<body>")]
+pub fn create_token(body: Json<TokenRequest>, conn: db::Connection) -> ApiResult<NewToken> {
+    issue_token(&body, &conn).map(Json)
+}
+
+pub fn issue_token(body: &TokenRequest, conn: &PgConnection) -> Result<NewToken, ApiError> {
+    let user = match User::get_by_email(body.email.trim(), conn)? {
+        Some(user) if Password::get_match(&user.id, &body.password, conn)?.is_some() => user,
+        _ => return Err(ApiError(Status::Unauthorized, "wrong email or password")),
+    };
+    let token = new_token();
+    let hash = token_hash(&token);
+    let saved = ApiToken::insert(
+        &NewApiToken {
+            user_id: &user.id,
+            name: body.name.as_deref().unwrap_or("api"),
+            token_hash: &hash,
+        },
+        conn,
+    )?;
+    Ok(NewToken { id: saved.id, token })
+}
+
+/// Signs the device out.
+#[delete("/tokens/current")]
+pub fn delete_token(api: ApiUser, conn: db::Connection) -> Result<Status, ApiError> {
+    ApiToken::delete(&api.token_id, &conn)?;
+    Ok(Status::NoContent)
+}
+
+/// The card to study now, `null` if there are no words at all.
+#[get("/cards/next?<book>")]
+pub fn next_card(api: ApiUser, book: Option<UuidParam>, conn: db::Connection) -> ApiResult<Option<Card>> {
+    let user = &api.user;
+    let book_id = match book {
+        Some(id) => match VocabBook::get_owned(&id, &user.id, &conn)? {
+            Some(book) => Some(book.id),
+            None => return Err(ApiError(Status::NotFound, "no such book")),
+        },
+        None => None,
+    };
+    let scope = book_id.as_ref().map_or(WordScope::AllWords, WordScope::Book);
+    let word = match VocabProgress::next_due(&user.id, scope, false, Utc::now(), &conn)? {
+        Some(word) => word,
+        None => return Ok(Json(None)),
+    };
+    let vocab = Vocab::get(&word, &conn)?.ok_or(ApiError(Status::NotFound, "no such word"))?;
+    let progress = VocabProgress::get(&user.id, &word, &conn)?;
+    let audio_url = if VocabSpeech::exists(&word, &conn)? {
+        Some(uri!(crate::get_prounciation: word.as_str()).to_string())
+    } else {
+        None
+    };
+    Ok(Json(Some(Card {
+        senses: VocabDict::get_by_word(&word, &conn)?,
+        state: match &progress {
+            None => Learning::New,
+            Some(p) if p.repetitions == 0 => Learning::Relearning,
+            Some(_) => Learning::Review,
+        },
+        word: vocab,
+        audio_url,
+        progress,
+    })))
+}
+
+/// Records the answer and returns when the card is due again.
+#[post("/cards/grade", format = "json", data = "<body>")]
+pub fn grade_card(api: ApiUser, body: Json<GradeRequest>, conn: db::Connection) -> ApiResult<VocabProgress> {
+    record_grade(&api.user.id, &body, Utc::now(), &conn).map(Json)
+}
+
+pub fn record_grade(
+    user_id: &Uuid,
+    body: &GradeRequest,
+    now: DateTime<Utc>,
+    conn: &PgConnection,
+) -> Result<VocabProgress, ApiError> {
+    if !Vocab::exists(&body.vocab, conn)? {
+        return Err(ApiError(Status::NotFound, "no such word"));
+    }
+    let action = match body.grade {
+        Grade::Forgot => ReviewAction::DontKnow,
+        Grade::Mistaken => ReviewAction::Wrong,
+        Grade::Recalled => ReviewAction::Right,
+    };
+    let latency_ms = body.latency_ms.unwrap_or(0).max(0).min(i32::MAX as i64) as i32;
+    ReviewLog::insert(user_id, &body.vocab, action, Some(body.grade), latency_ms, conn)?;
+    Vocab::count_review(&body.vocab, conn)?;
+    Ok(VocabProgress::review(user_id, &body.vocab, body.grade, now, conn)?)
+}
+
+#[get("/books")]
+pub fn books(api: ApiUser, conn: db::Connection) -> ApiResult<Vec<BookSummary>> {
+    let mut summaries = Vec::new();
+    for book in VocabBook::get_by_user(&api.user.id, &conn)? {
+        let word_count = VocabBookContent::count_by_book(&book.id, &conn)?;
+        summaries.push(BookSummary { book, word_count });
+    }
+    Ok(Json(summaries))
+}
+
+/// Words starting with `q` or with a meaning matching it, a page at a time like the search page.
+#[get("/search?<q>&<page>")]
+pub fn search(_api: ApiUser, q: String, page: Option<i64>, conn: db::Connection) -> ApiResult<SearchResults> {
+    let query = q.trim();
+    let page = page.unwrap_or(1).max(1);
+    let (words, pages) = if query.is_empty() {
+        (vec![], 0)
+    } else {
+        Vocab::search(query, page, words::PER_PAGE, &conn)?
+    };
+    let names: Vec<String> = words.iter().map(|w| w.vocab.clone()).collect();
+    let mut meanings: HashMap<String, Vec<String>> = HashMap::new();
+    for sense in VocabDict::get_by_words(&names, &conn)? {
+        meanings
+            .entry(sense.vocab)
+            .or_default()
+            .push(format!("{} {}", sense.partofspeech, sense.meaning));
+    }
+    let words = words
+        .into_iter()
+        .map(|word| SearchHit {
+            meanings: meanings.remove(&word.vocab).unwrap_or_default(),
+            url: words::word_url(&word.vocab),
+            vocab: word.vocab,
+        })
+        .collect();
+    Ok(Json(SearchResults {
+        query: query.to_string(),
+        page,
+        pages,
+        words,
+    }))
+}
+
+#[get("/stats")]
+pub fn stats(lang: ServerAcceptLangauge, api: ApiUser, conn: db::Connection) -> ApiResult<Stats> {
+    Ok(Json(user_stats(&api.user.id, TEXT[&lang]["book-all-words"], Utc::now(), &conn)?))
+}
+
+/// `all_words` names the accuracy over every word.
+pub fn user_stats(user_id: &Uuid, all_words: &str, now: DateTime<Utc>, conn: &PgConnection) -> QueryResult<Stats> {
+    Ok(Stats {
+        studied: VocabProgress::count_for_user(user_id, conn)?,
+        due: VocabProgress::count_due(user_id, now, conn)?,
+        progress: Progress::load(user_id, all_words, now, conn)?,
+    })
+}
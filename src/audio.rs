@@ -0,0 +1,587 @@
+// Pronunciation recordings for vocab_speeches, one file per word named after the word.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::Cursor,
+    path::Path,
+};
+
+use rocket::{
+    data::Data,
+    http::{ContentType, Status},
+    request::Request,
+    response::{self, Responder, Response},
+};
+use rocket_contrib::databases::diesel::{Connection, PgConnection};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use maud::{html, Markup};
+use strum::{EnumString, IntoStaticStr};
+use zip::ZipArchive;
+
+use crate::{
+    account::Admin,
+    anki::MAX_UNPACKED_BYTES,
+    app_page_view, db, db::*,
+    i18n::fill,
+    import::{read_limited, upload_js},
+    types::ServerAcceptLangauge,
+    TEXT,
+};
+
+const MAX_UPLOAD_BYTES: u64 = 256 * 1024 * 1024;
+// a word, not a sentence
+const MIN_DURATION_SECS: f64 = 0.1;
+const MAX_DURATION_SECS: f64 = 15.0;
+// the web report lists this many words without audio, the command prints all
+const MISSING_SHOWN: usize = 200;
+// played for words nobody recorded
+const FALLBACK_PATH: &str = "static/herald.mp3";
+// recordings are rarely replaced, and then the etag no longer matches
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumString, IntoStaticStr)]
+pub enum AudioFormat {
+    #[strum(serialize = "mp3")]
+    Mp3,
+    #[strum(serialize = "ogg")]
+    Ogg,
+}
+
+impl AudioFormat {
+    pub fn content_type(self) -> ContentType {
+        match self {
+            AudioFormat::Mp3 => ContentType::new("audio", "mpeg"),
+            AudioFormat::Ogg => ContentType::new("audio", "ogg"),
+        }
+    }
+}
+
+/// A recording answered with its etag and last modified time, honouring conditional and range requests.
+pub struct AudioResponse {
+    bytes: Vec<u8>,
+    format: AudioFormat,
+    // etag and last modified, `None` for the fallback
+    version: Option<(String, DateTime<Utc>)>,
+}
+
+impl AudioResponse {
+    pub fn recording(speech: VocabSpeech) -> AudioResponse {
+        AudioResponse {
+            format: speech.format.parse().unwrap_or(AudioFormat::Mp3),
+            version: Some((
+                format!("\"{}-{}\"", speech.id, speech.updated_at.timestamp_millis()),
+                speech.updated_at,
+            )),
+            bytes: speech.mp3,
+        }
+    }
+
+    /// The bundled herald sound for words without a recording, not cached as one may be added.
+    pub fn fallback() -> Result<AudioResponse> {
+        Ok(AudioResponse {
+            bytes: fs::read(FALLBACK_PATH).with_context(|| format!("reading {}", FALLBACK_PATH))?,
+            format: AudioFormat::Mp3,
+            version: None,
+        })
+    }
+}
+
+/// The byte range asked for as `start..end`, `None` to send everything and `Some(Err(()))` if it is out of bounds.
+pub fn byte_range(header: &str, len: usize) -> Option<Result<(usize, usize), ()>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    // several ranges need a multipart answer, the whole file will do
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return None,
+        ("", suffix) => (len.saturating_sub(suffix.parse().ok()?), len),
+        (start, "") => (start.parse().ok()?, len),
+        (start, end) => {
+            let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, end.saturating_add(1).min(len))
+        }
+    };
+    if start >= end {
+        return Some(Err(()));
+    }
+    Some(Ok((start, end)))
+}
+
+fn http_date(time: &DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+impl<'r> Responder<'r> for AudioResponse {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = Response::build();
+        response
+            .header(self.format.content_type())
+            .raw_header("Accept-Ranges", "bytes");
+        let (etag, last_modified) = match self.version {
+            Some(version) => version,
+            // a 404 would not be played by <audio>
+            None => {
+                return response
+                    .raw_header("Cache-Control", "no-cache")
+                    .sized_body(Cursor::new(self.bytes))
+                    .ok();
+            }
+        };
+        response
+            .raw_header("ETag", etag.clone())
+            .raw_header("Last-Modified", http_date(&last_modified))
+            .raw_header("Cache-Control", CACHE_CONTROL);
+
+        let headers = request.headers();
+        let not_modified = match headers.get_one("If-None-Match") {
+            Some(tags) => tags
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag),
+            None => headers
+                .get_one("If-Modified-Since")
+                .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+                .map_or(false, |since| last_modified.timestamp() <= since.timestamp()),
+        };
+        if not_modified {
+            return response.status(Status::NotModified).ok();
+        }
+
+        let len = self.bytes.len();
+        // a range of an older version would be garbage
+        let range = match headers.get_one("If-Range") {
+            Some(tag) if tag.trim() != etag && tag.trim() != http_date(&last_modified) => None,
+            _ => headers.get_one("Range").and_then(|range| byte_range(range, len)),
+        };
+        match range {
+            None => response.sized_body(Cursor::new(self.bytes)).ok(),
+            Some(Ok((start, end))) => {
+                let mut bytes = self.bytes;
+                bytes.truncate(end);
+                bytes.drain(..start);
+                response
+                    .status(Status::PartialContent)
+                    .raw_header("Content-Range", format!("bytes {}-{}/{}", start, end - 1, len))
+                    .sized_body(Cursor::new(bytes))
+                    .ok()
+            }
+            Some(Err(())) => response
+                .status(Status::RangeNotSatisfiable)
+                .raw_header("Content-Range", format!("bytes */{}", len))
+                .ok(),
+        }
+    }
+}
+
+/// Why a file is not stored as a recording, worded in the language of the page and in English on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    NotAudio,
+    TruncatedOgg,
+    OggCodec,
+    NoSampleRate,
+    /// the duration in seconds
+    Length(f64),
+    UnknownWord,
+    /// the word another file was stored for
+    Taken(String),
+    NotZip,
+    ZipTooBig,
+    /// as the zip reader put it
+    Unreadable(String),
+}
+
+impl Rejection {
+    pub fn text(&self, lang: &ServerAcceptLangauge) -> String {
+        let text = &TEXT[lang];
+        match self {
+            Rejection::NotAudio => text["audio-not-audio"].to_string(),
+            Rejection::TruncatedOgg => text["audio-truncated-ogg"].to_string(),
+            Rejection::OggCodec => text["audio-ogg-codec"].to_string(),
+            Rejection::NoSampleRate => text["audio-no-sample-rate"].to_string(),
+            Rejection::Length(duration) => fill(
+                text["audio-length"],
+                &[&format!("{:.1}", duration), &MIN_DURATION_SECS, &MAX_DURATION_SECS],
+            ),
+            Rejection::UnknownWord => text["audio-unknown-word"].to_string(),
+            Rejection::Taken(vocab) => fill(text["audio-taken"], &[vocab]),
+            Rejection::NotZip => text["audio-not-zip"].to_string(),
+            Rejection::ZipTooBig => fill(text["audio-zip-too-big"], &[&(MAX_UNPACKED_BYTES >> 20)]),
+            Rejection::Unreadable(e) => e.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text(&ServerAcceptLangauge::English))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct AudioReport {
+    pub stored: usize,
+    pub replaced: usize,
+    /// file name and why it was not stored
+    pub rejected: Vec<(String, Rejection)>,
+    /// words still without a recording
+    pub missing: Vec<String>,
+}
+
+/// The format and duration in seconds, or why the file is not a usable recording.
+pub fn probe(bytes: &[u8]) -> Result<(AudioFormat, f64), Rejection> {
+    let (format, duration) = if bytes.starts_with(b"OggS") {
+        (AudioFormat::Ogg, ogg_duration(bytes)?)
+    } else {
+        (AudioFormat::Mp3, mp3_duration(bytes)?)
+    };
+    if duration < MIN_DURATION_SECS || duration > MAX_DURATION_SECS {
+        return Err(Rejection::Length(duration));
+    }
+    Ok((format, duration))
+}
+
+struct Mp3Frame {
+    bitrate: u32,
+    sample_rate: u32,
+    samples: u32,
+    side_info_len: usize,
+}
+
+// MPEG audio layer III frame header, see http://www.mp3-tech.org/programmer/frame_header.html
+fn mp3_frame(header: &[u8]) -> Option<Mp3Frame> {
+    const MPEG1_KBPS: [u32; 16] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0];
+    const MPEG2_KBPS: [u32; 16] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0];
+    const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+    if header.len() < 4 || header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+    let version = (header[1] >> 3) & 0b11;
+    let layer = (header[1] >> 1) & 0b11;
+    let bitrate_idx = (header[2] >> 4) as usize;
+    let sample_rate_idx = ((header[2] >> 2) & 0b11) as usize;
+    let mono = header[3] >> 6 == 0b11;
+    // 0b01 is layer III, 0b01 version is reserved
+    if layer != 0b01 || version == 0b01 || sample_rate_idx == 3 {
+        return None;
+    }
+    let mpeg1 = version == 0b11;
+    let bitrate = (if mpeg1 { MPEG1_KBPS } else { MPEG2_KBPS })[bitrate_idx] * 1000;
+    let divisor = match version {
+        0b11 => 1,
+        0b10 => 2,
+        _ => 4,
+    };
+    if bitrate == 0 {
+        return None;
+    }
+    Some(Mp3Frame {
+        bitrate,
+        sample_rate: SAMPLE_RATES[sample_rate_idx] / divisor,
+        samples: if mpeg1 { 1152 } else { 576 },
+        side_info_len: match (mpeg1, mono) {
+            (true, true) => 17,
+            (true, false) => 32,
+            (false, true) => 9,
+            (false, false) => 17,
+        },
+    })
+}
+
+fn mp3_duration(bytes: &[u8]) -> Result<f64, Rejection> {
+    let mut start = 0;
+    if bytes.starts_with(b"ID3") && bytes.len() >= 10 {
+        // syncsafe size, 7 bits a byte, plus the footer if there is one
+        let size = bytes[6..10].iter().fold(0usize, |size, b| (size << 7) | (*b as usize & 0x7F));
+        let footer = if bytes[5] & 0x10 != 0 { 10 } else { 0 };
+        start = 10 + size + footer;
+    }
+    // some encoders leave padding before the first frame
+    let frame_start = (start..bytes.len().min(start + 64 * 1024))
+        .find(|i| mp3_frame(&bytes[*i..]).is_some())
+        .ok_or(Rejection::NotAudio)?;
+    let frame = mp3_frame(&bytes[frame_start..]).unwrap();
+
+    // a Xing/Info header in the first frame counts the frames of a VBR file
+    let xing = frame_start + 4 + frame.side_info_len;
+    if let Some(tag) = bytes.get(xing..xing + 12) {
+        if &tag[..4] == b"Xing" || &tag[..4] == b"Info" {
+            let flags = u32::from_be_bytes([tag[4], tag[5], tag[6], tag[7]]);
+            if flags & 1 != 0 {
+                let frames = u32::from_be_bytes([tag[8], tag[9], tag[10], tag[11]]);
+                return Ok(frames as f64 * frame.samples as f64 / frame.sample_rate as f64);
+            }
+        }
+    }
+    Ok((bytes.len() - frame_start) as f64 * 8.0 / frame.bitrate as f64)
+}
+
+// the last page's granule position counts samples, the first page tells the codec and its rate
+fn ogg_duration(bytes: &[u8]) -> Result<f64, Rejection> {
+    let segments = *bytes.get(26).ok_or(Rejection::TruncatedOgg)? as usize;
+    let body = bytes.get(27 + segments..).ok_or(Rejection::TruncatedOgg)?;
+    let (sample_rate, pre_skip) = if body.starts_with(b"\x01vorbis") && body.len() >= 16 {
+        (u32::from_le_bytes([body[12], body[13], body[14], body[15]]), 0)
+    } else if body.starts_with(b"OpusHead") && body.len() >= 12 {
+        // opus always counts at 48kHz
+        (48000, u16::from_le_bytes([body[10], body[11]]) as i64)
+    } else {
+        return Err(Rejection::OggCodec);
+    };
+    if sample_rate == 0 {
+        return Err(Rejection::NoSampleRate);
+    }
+    let last_page = (0..=bytes.len() - 4)
+        .rev()
+        .find(|i| &bytes[*i..*i + 4] == b"OggS")
+        .unwrap_or(0);
+    let granule = bytes
+        .get(last_page + 6..last_page + 14)
+        .ok_or(Rejection::TruncatedOgg)?;
+    let mut position = [0u8; 8];
+    position.copy_from_slice(granule);
+    Ok((i64::from_le_bytes(position) - pre_skip).max(0) as f64 / sample_rate as f64)
+}
+
+/// "Ice_Cream.mp3" is "Ice Cream", matched against the words exactly and then ignoring case.
+pub fn vocab_for_file<'a>(file_name: &str, words: &'a HashMap<String, String>) -> Option<&'a str> {
+    let base = file_name.rsplit(|c| c == '/' || c == '\\').next().unwrap_or(file_name);
+    let stem = match base.rfind('.') {
+        Some(dot) if dot > 0 => &base[..dot],
+        _ => base,
+    };
+    let name = stem.replace('_', " ").split_whitespace().collect::<Vec<_>>().join(" ");
+    words
+        .get(&name)
+        .or_else(|| words.get(&name.to_lowercase()))
+        .map(String::as_str)
+}
+
+/// Looks up a word by itself and by its lowercase form, the word itself wins.
+pub fn word_index(words: Vec<String>) -> HashMap<String, String> {
+    let mut index = HashMap::new();
+    for word in &words {
+        index.entry(word.to_lowercase()).or_insert_with(|| word.clone());
+    }
+    for word in words {
+        index.insert(word.clone(), word);
+    }
+    index
+}
+
+// finder and archiver litter, not recordings
+fn is_junk(name: &str) -> bool {
+    let base = name.rsplit('/').next().unwrap_or(name);
+    name.contains("__MACOSX/") || base.starts_with('.') || base.is_empty()
+}
+
+fn read_dir(dir: &Path, files: &mut Vec<(String, Vec<u8>)>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_dir(&path, files)?;
+        } else if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            if !is_junk(name) {
+                files.push((name.to_string(), fs::read(&path)?));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The recordings in a zip, which may unpack to `budget` bytes at most.
+pub fn read_zip(bytes: &[u8], mut budget: u64) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).context(Rejection::NotZip)?;
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.is_dir() || is_junk(file.name()) {
+            continue;
+        }
+        let name = file.name().to_string();
+        let content = read_limited(file, budget)?.context(Rejection::ZipTooBig)?;
+        budget -= content.len() as u64;
+        files.push((name, content));
+    }
+    Ok(files)
+}
+
+/// Stores every valid recording that names a known word, replacing what the word had, in one transaction.
+pub fn ingest(mut files: Vec<(String, Vec<u8>)>, connection: &PgConnection) -> Result<AudioReport> {
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    connection.transaction(|| {
+        let words = word_index(Vocab::all(connection)?);
+        let mut report = AudioReport::default();
+        let mut seen = HashSet::new();
+        for (name, bytes) in &files {
+            let vocab = match vocab_for_file(name, &words) {
+                Some(vocab) => vocab,
+                None => {
+                    report.rejected.push((name.clone(), Rejection::UnknownWord));
+                    continue;
+                }
+            };
+            let format = match probe(bytes) {
+                Ok((format, _)) => format,
+                Err(reason) => {
+                    report.rejected.push((name.clone(), reason));
+                    continue;
+                }
+            };
+            if !seen.insert(vocab) {
+                report.rejected.push((name.clone(), Rejection::Taken(vocab.to_string())));
+                continue;
+            }
+            let speech = NewVocabSpeech {
+                vocab,
+                mp3: bytes,
+                format: format.into(),
+            };
+            if VocabSpeech::upsert(&speech, connection)? {
+                report.replaced += 1;
+            } else {
+                report.stored += 1;
+            }
+        }
+        report.missing = Vocab::without_speech(connection)?;
+        Ok(report)
+    })
+}
+
+/// `beidanci audio <directory or zip>`
+pub fn run_cli(args: &[String]) -> Result<()> {
+    let source = match args {
+        [source] => Path::new(source),
+        _ => return Err(anyhow::anyhow!("usage: beidanci audio <directory or zip>")),
+    };
+    let files = if source.is_dir() {
+        let mut files = Vec::new();
+        read_dir(source, &mut files)?;
+        files
+    } else {
+        read_zip(
+            &fs::read(source).with_context(|| format!("reading {}", source.display()))?,
+            MAX_UNPACKED_BYTES,
+        )?
+    };
+
+    let connection = db::establish()?;
+    let report = ingest(files, &connection)?;
+    println!("{} stored, {} replaced", report.stored, report.replaced);
+    for (name, reason) in &report.rejected {
+        println!("{}: {}", name, reason);
+    }
+    println!("{} words without audio", report.missing.len());
+    for word in &report.missing {
+        println!("  {}", word);
+    }
+    Ok(())
+}
+
+#[get("/admin/audio")]
+pub fn audio_page(lang: ServerAcceptLangauge, admin: Admin) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        &admin.0,
+        &None,
+        html! {
+            h1 class="title" { (text["audio-title"]) }
+            p class="block" { (text["audio-help"]) }
+            form id="audio-form" {
+                div class="field has-addons" {
+                    div class="control is-expanded" {
+                        input class="input" type="file" id="audio-file" accept=".zip" required? {}
+                    }
+                    div class="control" {
+                        button class="button is-primary" type="submit" { (text["import-submit"]) }
+                    }
+                }
+            }
+            (upload_js("audio-form", "audio-file", "'/admin/audio'"))
+        },
+    )
+}
+
+#[post("/admin/audio", data = "<data>")]
+pub fn audio_upload(
+    lang: ServerAcceptLangauge,
+    admin: Admin,
+    data: Data,
+    conn: db::Connection,
+) -> Result<Result<Markup, Status>> {
+    let bytes = match read_limited(data.open(), MAX_UPLOAD_BYTES)? {
+        Some(bytes) => bytes,
+        None => return Ok(Err(Status::PayloadTooLarge)),
+    };
+    let report = match read_zip(&bytes, MAX_UNPACKED_BYTES) {
+        Ok(files) => ingest(files, &conn)?,
+        Err(e) => {
+            let rejection = match e.downcast_ref::<Rejection>() {
+                Some(rejection) => rejection.clone(),
+                None => Rejection::Unreadable(format!("{:#}", e)),
+            };
+            AudioReport {
+                rejected: vec![("upload".to_string(), rejection)],
+                ..AudioReport::default()
+            }
+        }
+    };
+    Ok(Ok(audio_report_view(lang, &admin.0, &report)))
+}
+
+fn audio_report_view(lang: ServerAcceptLangauge, user: &User, report: &AudioReport) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        user,
+        &None,
+        html! {
+            h1 class="title" { (text["audio-title"]) }
+            nav class="level" {
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["audio-stored"]) } p class="title" { (report.stored) } }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["audio-replaced"]) } p class="title" { (report.replaced) } }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["import-failed"]) } p class="title has-text-danger" { (report.rejected.len()) } }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["audio-missing"]) } p class="title" { (report.missing.len()) } }
+                }
+            }
+            @if !report.rejected.is_empty() {
+                h2 class="subtitle" { (text["import-failed"]) }
+                table class="table is-fullwidth" {
+                    tbody {
+                        @for (name, reason) in &report.rejected {
+                            tr { td { (name) } td { (reason.text(&lang)) } }
+                        }
+                    }
+                }
+            }
+            @if !report.missing.is_empty() {
+                h2 class="subtitle" { (text["audio-missing"]) }
+                div class="tags" {
+                    @for word in report.missing.iter().take(MISSING_SHOWN) {
+                        span class="tag" { (word) }
+                    }
+                    @if report.missing.len() > MISSING_SHOWN {
+                        span class="tag is-white" { "…" }
+                    }
+                }
+            }
+            a class="button" href=(uri!(audio_page).to_string()) { (text["import-again"]) }
+        },
+    )
+}
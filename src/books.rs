@@ -0,0 +1,444 @@
+use rocket::{
+    request::{FlashMessage, Form},
+    response::{Flash, Redirect},
+};
+use rocket_contrib::uuid::Uuid as UuidParam;
+
+use maud::{html, Markup};
+use uuid::Uuid;
+
+use crate::{app_page_view, db, db::*, flash_text, words, TEXT};
+use crate::types::ServerAcceptLangauge;
+
+use anyhow::Result;
+
+/// What the user chose to study.
+#[derive(Debug)]
+pub enum Choosen {
+    AllWords,
+    Book(VocabBook),
+    /// the words the user liked
+    Favorites,
+}
+
+impl Choosen {
+    pub fn scope<'a>(&'a self, user_id: &'a Uuid) -> WordScope<'a> {
+        match self {
+            Choosen::AllWords => WordScope::AllWords,
+            Choosen::Book(book) => WordScope::Book(&book.id),
+            Choosen::Favorites => WordScope::Favorites(user_id),
+        }
+    }
+
+    fn is_book(&self, id: &Uuid) -> bool {
+        match self {
+            Choosen::Book(book) => book.id == *id,
+            Choosen::AllWords | Choosen::Favorites => false,
+        }
+    }
+}
+
+pub fn choosen_book(session: &StudySession, user: &User, conn: &db::Connection) -> Result<Choosen> {
+    if session.favorites {
+        return Ok(Choosen::Favorites);
+    }
+    match &session.book_id {
+        // a foreign book falls back to every word, a deleted one is already unset
+        Some(id) => Ok(VocabBook::get_owned(id, &user.id, conn)?.map_or(Choosen::AllWords, Choosen::Book)),
+        None => Ok(Choosen::AllWords),
+    }
+}
+
+pub fn book_url(id: &Uuid) -> String {
+    format!("/books/{}", id)
+}
+
+#[derive(Debug, FromForm)]
+pub struct BookForm {
+    name: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct BookWordForm {
+    vocab: String,
+}
+
+#[get("/books")]
+pub fn books_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    conn: db::Connection,
+) -> Result<Markup> {
+    let flash_msg = flash_text(flash);
+    let session = StudySession::get_or_start(&user.id, &conn)?;
+    let choosen = choosen_book(&session, &user, &conn)?;
+    let books = VocabBook::get_by_user(&user.id, &conn)?
+        .into_iter()
+        .map(|book| -> Result<(VocabBook, i64)> {
+            let count = VocabBookContent::count_by_book(&book.id, &conn)?;
+            Ok((book, count))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let favorites = VocabLike::count_by_user(&user.id, &conn)?;
+    Ok(books_view(lang, &user, &flash_msg, &books, favorites, &choosen))
+}
+
+#[post("/books", data = "<form>")]
+pub fn create_book(
+    lang: ServerAcceptLangauge,
+    user: User,
+    form: Form<BookForm>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Ok(Flash::warning(Redirect::to(uri!(books_page)), TEXT[&lang]["book-empty-name"]));
+    }
+    let book = VocabBook::insert(name, &user.id, &conn)?;
+    Ok(Flash::success(Redirect::to(book_url(&book.id)), TEXT[&lang]["book-created"]))
+}
+
+#[get("/books/<id>")]
+pub fn book_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    id: UuidParam,
+    conn: db::Connection,
+) -> Result<Option<Markup>> {
+    let flash_msg = flash_text(flash);
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    let session = StudySession::get_or_start(&user.id, &conn)?;
+    let studying = choosen_book(&session, &user, &conn)?.is_book(&book.id);
+    let words = VocabBookContent::get_by_book(&book.id, &conn)?;
+    Ok(Some(book_view(lang, &user, &flash_msg, &book, &words, studying)))
+}
+
+#[post("/books/<id>/rename", data = "<form>")]
+pub fn rename_book(
+    lang: ServerAcceptLangauge,
+    user: User,
+    id: UuidParam,
+    form: Form<BookForm>,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Ok(Some(Flash::warning(Redirect::to(book_url(&book.id)), TEXT[&lang]["book-empty-name"])));
+    }
+    VocabBook::rename(&book.id, name, &conn)?;
+    Ok(Some(Flash::success(Redirect::to(book_url(&book.id)), TEXT[&lang]["book-renamed"])))
+}
+
+#[post("/books/<id>/delete")]
+pub fn delete_book(
+    lang: ServerAcceptLangauge,
+    user: User,
+    id: UuidParam,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    VocabBook::delete(&book.id, &conn)?;
+    Ok(Some(Flash::success(Redirect::to(uri!(books_page)), TEXT[&lang]["book-deleted"])))
+}
+
+#[post("/books/<id>/words", data = "<form>")]
+pub fn add_book_word(
+    lang: ServerAcceptLangauge,
+    user: User,
+    id: UuidParam,
+    form: Form<BookWordForm>,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    let vocab = form.vocab.trim();
+    if !Vocab::exists(vocab, &conn)? {
+        return Ok(Some(Flash::warning(Redirect::to(book_url(&book.id)), TEXT[&lang]["word-unknown"])));
+    }
+    VocabBookContent::insert(&book.id, vocab, &conn)?;
+    Ok(Some(Flash::success(Redirect::to(book_url(&book.id)), TEXT[&lang]["book-word-added"])))
+}
+
+#[post("/books/<id>/words/remove", data = "<form>")]
+pub fn remove_book_word(
+    lang: ServerAcceptLangauge,
+    user: User,
+    id: UuidParam,
+    form: Form<BookWordForm>,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    VocabBookContent::delete(&book.id, &form.vocab, &conn)?;
+    Ok(Some(Flash::success(Redirect::to(book_url(&book.id)), TEXT[&lang]["book-word-removed"])))
+}
+
+#[post("/books/<id>/study")]
+pub fn study_book(user: User, id: UuidParam, conn: db::Connection) -> Result<Option<Redirect>> {
+    let book = match VocabBook::get_owned(&id, &user.id, &conn)? {
+        Some(book) => book,
+        None => return Ok(None),
+    };
+    StudySession::choose_book(&user.id, Some(&book.id), &conn)?;
+    Ok(Some(Redirect::to(uri!(crate::index))))
+}
+
+#[post("/books/study-all")]
+pub fn study_all(user: User, conn: db::Connection) -> Result<Redirect> {
+    StudySession::choose_book(&user.id, None, &conn)?;
+    Ok(Redirect::to(uri!(crate::index)))
+}
+
+#[get("/books/favorites")]
+pub fn favorites_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    conn: db::Connection,
+) -> Result<Markup> {
+    let flash_msg = flash_text(flash);
+    let session = StudySession::get_or_start(&user.id, &conn)?;
+    let studying = session.favorites;
+    let words = VocabLike::get_by_user(&user.id, &conn)?;
+    Ok(favorites_view(lang, &user, &flash_msg, &words, studying))
+}
+
+#[post("/books/favorites/study")]
+pub fn study_favorites(user: User, conn: db::Connection) -> Result<Redirect> {
+    StudySession::choose_favorites(&user.id, &conn)?;
+    Ok(Redirect::to(uri!(crate::index)))
+}
+
+fn books_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    books: &[(VocabBook, i64)],
+    favorites: i64,
+    choosen: &Choosen,
+) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            div class="level" {
+                div class="level-left" {
+                    h1 class="level-item title" { (text["books-title"]) }
+                }
+                @if user.is_admin {
+                    div class="level-right" {
+                        a class="level-item button" href=(uri!(crate::anki::import_page).to_string()) { (text["anki-import-title"]) }
+                    }
+                }
+            }
+            form action=(uri!(create_book).to_string()) method="post" {
+                div class="field has-addons" {
+                    div class="control is-expanded" {
+                        input class="input" type="text" name="name" placeholder=(text["book-new-name"]) required? {}
+                    }
+                    div class="control" {
+                        button class="button is-primary" type="submit" { (text["book-create"]) }
+                    }
+                }
+            }
+            table class="table is-fullwidth is-hoverable" {
+                tbody {
+                    tr {
+                        td { (text["book-all-words"]) }
+                        td {}
+                        td class="has-text-right" {
+                            @if let Choosen::AllWords = choosen {
+                                span class="tag is-success" { (text["book-studying"]) }
+                            } @else {
+                                form action=(uri!(study_all).to_string()) method="post" {
+                                    button class="button is-small" type="submit" { (text["book-study"]) }
+                                }
+                            }
+                        }
+                    }
+                    tr {
+                        td { a href=(uri!(favorites_page).to_string()) { (text["book-favorites"]) } }
+                        td { (favorites) " " (text["book-word-count"]) }
+                        td class="has-text-right" {
+                            @if let Choosen::Favorites = choosen {
+                                span class="tag is-success" { (text["book-studying"]) }
+                            } @else if favorites > 0 {
+                                form action=(uri!(study_favorites).to_string()) method="post" {
+                                    button class="button is-small" type="submit" { (text["book-study"]) }
+                                }
+                            }
+                        }
+                    }
+                    @for (book, count) in books {
+                        tr {
+                            td { a href=(book_url(&book.id)) { (book.name) } }
+                            td { (count) " " (text["book-word-count"]) }
+                            td class="has-text-right" {
+                                @if choosen.is_book(&book.id) {
+                                    span class="tag is-success" { (text["book-studying"]) }
+                                } @else {
+                                    form action={(book_url(&book.id)) "/study"} method="post" {
+                                        button class="button is-small" type="submit" { (text["book-study"]) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn book_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    book: &VocabBook,
+    words: &[VocabBookContent],
+    studying: bool,
+) -> Markup {
+    let text = &TEXT[&lang];
+    let url = book_url(&book.id);
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            nav class="breadcrumb" {
+                ul {
+                    li { a href=(uri!(books_page).to_string()) { (text["books-title"]) } }
+                    li class="is-active" { a href=(url) { (book.name) } }
+                }
+            }
+            div class="level" {
+                div class="level-left" {
+                    form class="level-item" action={(url) "/rename"} method="post" {
+                        div class="field has-addons" {
+                            div class="control" {
+                                input class="input" type="text" name="name" value=(book.name) required? {}
+                            }
+                            div class="control" {
+                                button class="button" type="submit" { (text["book-rename"]) }
+                            }
+                        }
+                    }
+                }
+                div class="level-right" {
+                    @if studying {
+                        span class="level-item tag is-success" { (text["book-studying"]) }
+                    } @else {
+                        form class="level-item" action={(url) "/study"} method="post" {
+                            button class="button is-primary" type="submit" { (text["book-study"]) }
+                        }
+                    }
+                    a class="level-item button" href={(url) "/export.apkg"} { (text["anki-export"]) }
+                    form class="level-item" action={(url) "/delete"} method="post"
+                         onsubmit={"return confirm('" (text["book-delete-confirm"]) "');"} {
+                        button class="button is-danger is-outlined" type="submit" { (text["book-delete"]) }
+                    }
+                }
+            }
+            form action={(url) "/words"} method="post" {
+                div class="field has-addons" {
+                    div class="control is-expanded" {
+                        input class="input" type="text" name="vocab" placeholder=(text["book-add-word"]) required? {}
+                    }
+                    div class="control" {
+                        button class="button is-primary" type="submit" { (text["book-add-word"]) }
+                    }
+                }
+            }
+            table class="table is-fullwidth is-hoverable" {
+                thead {
+                    tr {
+                        th { (words.len()) " " (text["book-word-count"]) }
+                        th {}
+                    }
+                }
+                tbody {
+                    @for word in words {
+                        tr {
+                            td { a href=(words::word_url(&word.vocab)) { (word.vocab) } }
+                            td class="has-text-right" {
+                                form action={(url) "/words/remove"} method="post" {
+                                    input type="hidden" name="vocab" value=(word.vocab) {}
+                                    button class="delete" type="submit" title=(text["book-remove-word"]) {}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn favorites_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    words: &[VocabLike],
+    studying: bool,
+) -> Markup {
+    let text = &TEXT[&lang];
+    let url = uri!(favorites_page).to_string();
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            nav class="breadcrumb" {
+                ul {
+                    li { a href=(uri!(books_page).to_string()) { (text["books-title"]) } }
+                    li class="is-active" { a href=(url) { (text["book-favorites"]) } }
+                }
+            }
+            div class="level" {
+                div class="level-left" {
+                    span class="level-item" { (words.len()) " " (text["book-word-count"]) }
+                }
+                div class="level-right" {
+                    @if studying {
+                        span class="level-item tag is-success" { (text["book-studying"]) }
+                    } @else if !words.is_empty() {
+                        form class="level-item" action=(uri!(study_favorites).to_string()) method="post" {
+                            button class="button is-primary" type="submit" { (text["book-study"]) }
+                        }
+                    }
+                }
+            }
+            @if words.is_empty() {
+                p class="has-text-grey" { (text["favorites-empty"]) }
+            }
+            table class="table is-fullwidth is-hoverable" {
+                tbody {
+                    @for like in words {
+                        tr {
+                            td { a href=(words::word_url(&like.vocab)) { (like.vocab) } }
+                            td class="has-text-right" { (words::like_button(&lang, &like.vocab, true, &url)) }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
@@ -4,26 +4,76 @@ mod schema;
 use rocket::request::FromForm;
 use rocket_contrib::databases::{
     diesel,
-    diesel::{prelude::*, PgConnection, QueryResult},
+    diesel::{dsl, prelude::*, sql_types, sql_types::Text, PgConnection, QueryResult},
 };
 
 #[database("postgres")]
 pub struct Connection(diesel::PgConnection);
 
+/// A connection for commands run outside of Rocket, to the same database as the pool.
+pub fn establish() -> anyhow::Result<PgConnection> {
+    let rocket = rocket::ignite();
+    let config = rocket_contrib::databases::database_config("postgres", rocket.config())
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(PgConnection::establish(config.url)?)
+}
+
+use serde::Serialize;
 use uuid::Uuid;
-use chrono::DateTime;
+use chrono::{DateTime, NaiveDate};
 use chrono::offset::Utc;
 
 use paginate::*;
 use schema::*;
 
-#[derive(Queryable, Debug, Identifiable)]
+use crate::scheduler::{Grade, Schedule};
+use crate::types::{CardState, PanelRankType, ReviewAction, StudyMode};
+
+/// `text` matched literally by `LIKE`.
+pub fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// The words cards are drawn from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WordScope<'a> {
+    AllWords,
+    Book(&'a Uuid),
+    /// the words liked by this user
+    Favorites(&'a Uuid),
+}
+
+/// Keeps the rows of a boxed `query` whose word `column` is in `scope`.
+macro_rules! filter_scope {
+    ($query:expr, $column:expr, $scope:expr) => {
+        match $scope {
+            WordScope::AllWords => $query,
+            WordScope::Book(book_id) => $query.filter($column.eq_any(VocabBookContent::words_of(book_id))),
+            WordScope::Favorites(user_id) => $query.filter($column.eq_any(VocabLike::words_of(user_id))),
+        }
+    };
+}
+
+#[table_name = "users"]
+#[derive(Queryable, QueryableByName, Debug, Identifiable)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub is_admin: bool,
+}
+
+#[table_name = "passwords"]
+#[derive(Queryable, QueryableByName, Debug, Identifiable)]
+#[primary_key(user_id)]
+pub struct Password {
+    pub user_id: Uuid,
+    pub passwd: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Queryable, Debug, Identifiable)]
@@ -35,7 +85,7 @@ pub struct VocabBookContent {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Queryable, Debug, Identifiable)]
+#[derive(Queryable, Serialize, Debug, Identifiable)]
 pub struct VocabBook {
     pub id: Uuid,
     pub name: String,
@@ -44,7 +94,37 @@ pub struct VocabBook {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Queryable, Debug, Identifiable)]
+#[table_name = "vocab_books"]
+#[derive(Insertable, Debug)]
+pub struct NewVocabBook<'a> {
+    pub name: &'a str,
+    pub created_by: &'a Uuid,
+}
+
+#[table_name = "vocab_dicts"]
+#[derive(Insertable, Debug)]
+pub struct NewVocabDict<'a> {
+    pub vocab: &'a str,
+    pub partofspeech: &'a str,
+    pub meaning: &'a str,
+}
+
+#[table_name = "vocab_speeches"]
+#[derive(Insertable, AsChangeset, Debug)]
+pub struct NewVocabSpeech<'a> {
+    pub vocab: &'a str,
+    pub mp3: &'a [u8],
+    pub format: &'a str,
+}
+
+#[table_name = "vocab_book_contents"]
+#[derive(Insertable, Debug)]
+pub struct NewVocabBookContent<'a> {
+    pub book_id: &'a Uuid,
+    pub vocab: &'a str,
+}
+
+#[derive(Queryable, Serialize, Debug, Clone, PartialEq, Eq, Identifiable)]
 pub struct VocabDict {
     pub id: Uuid,
     pub vocab: String,
@@ -54,6 +134,13 @@ pub struct VocabDict {
     pub updated_at: DateTime<Utc>,
 }
 
+/// The meanings of a word sharing one part of speech.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenseGroup {
+    pub partofspeech: String,
+    pub meanings: Vec<String>,
+}
+
 #[table_name = "vocab_speeches"]
 #[derive(Queryable, Debug, Identifiable)]
 pub struct VocabSpeech {
@@ -62,53 +149,1107 @@ pub struct VocabSpeech {
     pub updated_at: DateTime<Utc>,
     pub vocab: String,
     pub mp3: Vec<u8>,
+    pub format: String,
 }
 
 #[table_name = "vocabs"]
-#[derive(Queryable, Debug, Identifiable)]
+#[derive(Queryable, Serialize, Debug, Identifiable)]
 #[primary_key(vocab)]
 pub struct Vocab {
     pub vocab: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// cards of this word finished by anyone
+    pub review_count: i32,
+    pub like_count: i32,
+}
+
+#[table_name = "vocab_progresses"]
+#[derive(Queryable, Serialize, Debug, Identifiable)]
+pub struct VocabProgress {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub vocab: String,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub lapses: i32,
+    pub due_at: DateTime<Utc>,
+    pub reviewed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[table_name = "vocab_progresses"]
+#[derive(Insertable, AsChangeset, Debug)]
+pub struct NewVocabProgress<'a> {
+    pub user_id: &'a Uuid,
+    pub vocab: &'a str,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub lapses: i32,
+    pub due_at: DateTime<Utc>,
+    pub reviewed_at: DateTime<Utc>,
+}
+
+#[table_name = "study_sessions"]
+#[derive(Queryable, Debug, Identifiable)]
+#[primary_key(user_id)]
+pub struct StudySession {
+    pub user_id: Uuid,
+    pub state: String,
+    pub vocab: Option<String>,
+    pub book_id: Option<Uuid>,
+    pub shown_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub mode: String,
+    pub answer: Option<String>,
+    /// studying the liked words, `book_id` is unset then
+    pub favorites: bool,
+    /// new whenever the card or its state changes, the study page sends it back instead of the word
+    pub token: Uuid,
+    /// the multiple choice question asked for the card, kept so reloading asks the same
+    pub choice_sense: Option<Uuid>,
+    pub choice_by_word: bool,
+    pub choice_options: Option<Vec<String>>,
+}
+
+#[table_name = "vocab_likes"]
+#[derive(Queryable, Debug, Identifiable)]
+#[primary_key(user_id, vocab)]
+pub struct VocabLike {
+    pub user_id: Uuid,
+    pub vocab: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[table_name = "vocab_likes"]
+#[derive(Insertable, Debug)]
+pub struct NewVocabLike<'a> {
+    pub user_id: &'a Uuid,
+    pub vocab: &'a str,
+}
+
+#[table_name = "vocab_notes"]
+#[derive(Queryable, Debug, Identifiable)]
+#[primary_key(user_id, vocab)]
+pub struct VocabNote {
+    pub user_id: Uuid,
+    pub vocab: String,
+    /// Markdown
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[table_name = "vocab_notes"]
+#[derive(Insertable, Debug)]
+pub struct NewVocabNote<'a> {
+    pub user_id: &'a Uuid,
+    pub vocab: &'a str,
+    pub body: &'a str,
+}
+
+#[table_name = "vocab_examples"]
+#[derive(Queryable, Debug, Identifiable)]
+pub struct VocabExample {
+    pub id: Uuid,
+    pub dict_id: Uuid,
+    /// Markdown
+    pub body: String,
+    /// `None` for imported examples
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[table_name = "vocab_examples"]
+#[derive(Insertable, Debug)]
+pub struct NewVocabExample<'a> {
+    pub dict_id: &'a Uuid,
+    pub body: &'a str,
+    pub created_by: Option<&'a Uuid>,
+}
+
+#[table_name = "review_logs"]
+#[derive(Queryable, Debug, Identifiable)]
+pub struct ReviewLog {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub vocab: String,
+    pub action: String,
+    pub latency_ms: i32,
+    pub created_at: DateTime<Utc>,
+    pub passed: Option<bool>,
+}
+
+#[table_name = "review_logs"]
+#[derive(Insertable, Debug)]
+pub struct NewReviewLog<'a> {
+    pub user_id: &'a Uuid,
+    pub vocab: &'a str,
+    pub action: &'static str,
+    pub latency_ms: i32,
+    pub passed: Option<bool>,
+}
+
+#[table_name = "api_tokens"]
+#[derive(Queryable, Debug, Identifiable)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: Vec<u8>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[table_name = "api_tokens"]
+#[derive(Insertable, Debug)]
+pub struct NewApiToken<'a> {
+    pub user_id: &'a Uuid,
+    pub name: &'a str,
+    pub token_hash: &'a [u8],
+}
+
+impl User {
+    pub fn get(id: &Uuid, connection: &PgConnection) -> QueryResult<User> {
+        users::table.find(id).get_result::<User>(connection)
+    }
+
+    pub fn get_by_username(name: &str, connection: &PgConnection) -> QueryResult<Option<User>> {
+        users::table
+            .filter(users::username.eq(name))
+            .get_result::<User>(connection)
+            .optional()
+    }
+
+    pub fn get_by_email(email: &str, connection: &PgConnection) -> QueryResult<Option<User>> {
+        diesel::sql_query("SELECT * FROM users WHERE lower(email) = lower($1)")
+            .bind::<Text, _>(email)
+            .get_result::<User>(connection)
+            .optional()
+    }
+
+    pub fn insert_with_password(
+        username: &str,
+        email: &str,
+        passwd: &str,
+        connection: &PgConnection,
+    ) -> QueryResult<User> {
+        connection.transaction(|| {
+            let user = diesel::insert_into(users::table)
+                .values((users::username.eq(username), users::email.eq(email)))
+                .get_result::<User>(connection)?;
+            diesel::sql_query(
+                "INSERT INTO passwords (user_id, passwd) VALUES ($1, crypt($2, gen_salt('bf')))",
+            )
+            .bind::<sql_types::Uuid, _>(&user.id)
+            .bind::<Text, _>(passwd)
+            .execute(connection)?;
+            Ok(user)
+        })
+    }
+
+    pub fn mark_email_verified(id: &Uuid, connection: &PgConnection) -> QueryResult<User> {
+        diesel::update(users::table.find(id))
+            .set(users::email_verified_at.eq(diesel::dsl::now))
+            .get_result(connection)
+    }
+}
+
+impl ApiToken {
+    pub fn insert(token: &NewApiToken, connection: &PgConnection) -> QueryResult<ApiToken> {
+        diesel::insert_into(api_tokens::table)
+            .values(token)
+            .get_result(connection)
+    }
+
+    /// The token with this hash and its owner, marking the token as used.
+    pub fn authenticate(
+        token_hash: &[u8],
+        connection: &PgConnection,
+    ) -> QueryResult<Option<(ApiToken, User)>> {
+        let token = diesel::update(api_tokens::table.filter(api_tokens::token_hash.eq(token_hash)))
+            .set(api_tokens::last_used_at.eq(dsl::now))
+            .get_result::<ApiToken>(connection)
+            .optional()?;
+        match token {
+            Some(token) => {
+                let user = User::get(&token.user_id, connection)?;
+                Ok(Some((token, user)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn delete(id: &Uuid, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(api_tokens::table.find(id)).execute(connection)
+    }
+}
+
+impl Password {
+    pub fn get(user_id: &Uuid, connection: &PgConnection) -> QueryResult<Password> {
+        passwords::table.find(user_id).get_result(connection)
+    }
+
+    pub fn update(user_id: &Uuid, passwd: &str, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::sql_query("UPDATE passwords SET passwd = crypt($2, gen_salt('bf')) WHERE user_id = $1")
+            .bind::<sql_types::Uuid, _>(user_id)
+            .bind::<Text, _>(passwd)
+            .execute(connection)
+    }
+
+    pub fn get_match(
+        user_id: &Uuid,
+        passwd: &str,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<Password>> {
+        diesel::sql_query(
+            "SELECT * FROM passwords WHERE user_id = $1 AND passwd = crypt($2, passwd)",
+        )
+        .bind::<sql_types::Uuid, _>(user_id)
+        .bind::<Text, _>(passwd)
+        .get_result::<Password>(connection)
+        .optional()
+    }
 }
 
 impl Vocab {
-    pub fn get_by_idx(idx: i64, connection: &PgConnection,
+    /// A page of the dictionary and the number of pages.
+    pub fn get_paginate(
+        rank: PanelRankType,
+        page_idx: i64,
+        limit: i64,
+        connection: &PgConnection,
+    ) -> QueryResult<(Vec<Vocab>, i64)> {
+        use std::cmp::max;
+        let query = vocabs::table.into_boxed();
+        let query = match rank {
+            PanelRankType::MostRecent => query.order((vocabs::created_at.desc(), vocabs::vocab.asc())),
+            PanelRankType::MostReview => query.order((vocabs::review_count.desc(), vocabs::vocab.asc())),
+            PanelRankType::MostLike => query.order((vocabs::like_count.desc(), vocabs::vocab.asc())),
+        };
+        query
+            .paginate(max(page_idx, 1))
+            .per_page(max(limit, 1))
+            .load_and_count_pages(connection)
+    }
+
+    pub fn all(connection: &PgConnection) -> QueryResult<Vec<String>> {
+        vocabs::table
+            .select(vocabs::vocab)
+            .order(vocabs::vocab.asc())
+            .load(connection)
+    }
+
+    pub fn without_speech(connection: &PgConnection) -> QueryResult<Vec<String>> {
+        vocabs::table
+            .select(vocabs::vocab)
+            .filter(vocabs::vocab.ne_all(VocabSpeech::words()))
+            .order(vocabs::vocab.asc())
+            .load(connection)
+    }
+
+    pub fn get(word: &str, connection: &PgConnection) -> QueryResult<Option<Vocab>> {
+        vocabs::table.find(word).get_result(connection).optional()
+    }
+
+    /// Counts one more finished card of `word` for ranking the word list by reviews.
+    pub fn count_review(word: &str, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::update(vocabs::table.find(word))
+            .set(vocabs::review_count.eq(vocabs::review_count + 1))
+            .execute(connection)
+    }
+
+    /// A page of the words starting with `query`, or with a meaning matching it, and the number of pages.
+    /// The word itself comes first, then the other words starting with it.
+    pub fn search(
+        query: &str,
+        page_idx: i64,
+        limit: i64,
+        connection: &PgConnection,
     ) -> QueryResult<(Vec<Vocab>, i64)> {
-            use std::cmp::max;
-            let page_idx = max(idx, 1);
-            let paginated_query = vocabs::table.order(vocabs::created_at.desc())
-                .paginate(page_idx)
-                .per_page(1);
-            // println!("{}",diesel::debug_query(&paginated_query));
-            paginated_query.load_and_count_pages(connection)
+        use std::cmp::max;
+        let escaped = escape_like(query);
+        let prefix = format!("{}%", escaped);
+        let meaning_matches = vocab_dicts::table.select(vocab_dicts::vocab).filter(
+            dsl::sql::<sql_types::Bool>("to_tsvector('simple', vocab_dicts.meaning) @@ plainto_tsquery('simple', ")
+                .bind::<Text, _>(query)
+                .sql(")")
+                .or(vocab_dicts::meaning.ilike(format!("%{}%", escaped))),
+        );
+        vocabs::table
+            .filter(vocabs::vocab.ilike(prefix.clone()).or(vocabs::vocab.eq_any(meaning_matches)))
+            .order((
+                vocabs::vocab.eq(query).desc(),
+                vocabs::vocab.ilike(prefix).desc(),
+                vocabs::vocab.asc(),
+            ))
+            .into_boxed()
+            .paginate(max(page_idx, 1))
+            .per_page(max(limit, 1))
+            .load_and_count_pages(connection)
+    }
+
+    pub fn exists(word: &str, connection: &PgConnection) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(vocabs::table.find(word))).get_result(connection)
+    }
+
+    pub fn count(connection: &PgConnection) -> QueryResult<i64> {
+        vocabs::table.count().get_result(connection)
+    }
+
+    /// Returns whether the word was new.
+    pub fn insert_if_missing(word: &str, connection: &PgConnection) -> QueryResult<bool> {
+        let inserted = diesel::insert_into(vocabs::table)
+            .values(vocabs::vocab.eq(word))
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+        Ok(inserted > 0)
+    }
+}
+
+impl VocabDict {
+    pub fn get(id: &Uuid, connection: &PgConnection) -> QueryResult<Option<VocabDict>> {
+        vocab_dicts::table.find(id).get_result(connection).optional()
+    }
+
+    pub fn get_by_word(word: &str, connection: &PgConnection) -> QueryResult<Vec<VocabDict>> {
+        vocab_dicts::table
+            .filter(vocab_dicts::vocab.eq(word))
+            .order(vocab_dicts::created_at.asc())
+            .get_results(connection)
+    }
+
+    pub fn get_by_words(words: &[String], connection: &PgConnection) -> QueryResult<Vec<VocabDict>> {
+        vocab_dicts::table
+            .filter(vocab_dicts::vocab.eq_any(words))
+            .order(vocab_dicts::created_at.asc())
+            .get_results(connection)
+    }
+
+    pub fn get_id(sense: &NewVocabDict, connection: &PgConnection) -> QueryResult<Uuid> {
+        vocab_dicts::table
+            .select(vocab_dicts::id)
+            .filter(vocab_dicts::vocab.eq(sense.vocab))
+            .filter(vocab_dicts::partofspeech.eq(sense.partofspeech))
+            .filter(vocab_dicts::meaning.eq(sense.meaning))
+            .get_result(connection)
+    }
+
+    /// Returns whether the sense was new.
+    pub fn insert_if_missing(sense: &NewVocabDict, connection: &PgConnection) -> QueryResult<bool> {
+        let inserted = diesel::insert_into(vocab_dicts::table)
+            .values(sense)
+            .on_conflict((vocab_dicts::vocab, vocab_dicts::partofspeech, vocab_dicts::meaning))
+            .do_nothing()
+            .execute(connection)?;
+        Ok(inserted > 0)
+    }
+
+    /// Up to `limit` random senses of other words in `scope` with this part of speech.
+    pub fn like(
+        word: &str,
+        partofspeech: &str,
+        scope: WordScope,
+        limit: i64,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<VocabDict>> {
+        let query = vocab_dicts::table
+            .filter(vocab_dicts::partofspeech.eq(partofspeech))
+            .filter(vocab_dicts::vocab.ne(word))
+            .into_boxed();
+        filter_scope!(query, vocab_dicts::vocab, scope)
+            .order(dsl::sql::<sql_types::Double>("random()"))
+            .limit(limit)
+            .get_results(connection)
+    }
+
+    /// Groups senses by part of speech, keeping the order they first appear in.
+    pub fn group_senses(senses: &[VocabDict]) -> Vec<SenseGroup> {
+        let mut groups: Vec<SenseGroup> = Vec::new();
+        for sense in senses {
+            match groups.iter_mut().find(|g| g.partofspeech == sense.partofspeech) {
+                Some(group) => group.meanings.push(sense.meaning.clone()),
+                None => groups.push(SenseGroup {
+                    partofspeech: sense.partofspeech.clone(),
+                    meanings: vec![sense.meaning.clone()],
+                }),
+            }
+        }
+        groups
+    }
+}
+
+impl VocabBook {
+    /// The book, only if `user_id` owns it.
+    pub fn get_owned(
+        id: &Uuid,
+        user_id: &Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<VocabBook>> {
+        vocab_books::table
+            .find(id)
+            .filter(vocab_books::created_by.eq(user_id))
+            .get_result::<VocabBook>(connection)
+            .optional()
+    }
+
+    pub fn get_by_user(user_id: &Uuid, connection: &PgConnection) -> QueryResult<Vec<VocabBook>> {
+        vocab_books::table
+            .filter(vocab_books::created_by.eq(user_id))
+            .order(vocab_books::created_at.asc())
+            .get_results(connection)
+    }
+
+    /// The user's books with `vocab` in them.
+    pub fn containing(user_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<Vec<VocabBook>> {
+        let books_with_word = vocab_book_contents::table
+            .select(vocab_book_contents::book_id)
+            .filter(vocab_book_contents::vocab.eq(vocab));
+        vocab_books::table
+            .filter(vocab_books::created_by.eq(user_id))
+            .filter(vocab_books::id.eq_any(books_with_word))
+            .order(vocab_books::created_at.asc())
+            .get_results(connection)
+    }
+
+    pub fn insert(name: &str, created_by: &Uuid, connection: &PgConnection) -> QueryResult<VocabBook> {
+        diesel::insert_into(vocab_books::table)
+            .values(&NewVocabBook { name, created_by })
+            .get_result(connection)
+    }
+
+    pub fn get_or_insert_by_name(
+        name: &str,
+        created_by: &Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<VocabBook> {
+        let book = vocab_books::table
+            .filter(vocab_books::created_by.eq(created_by))
+            .filter(vocab_books::name.eq(name))
+            .first::<VocabBook>(connection)
+            .optional()?;
+        match book {
+            Some(book) => Ok(book),
+            None => VocabBook::insert(name, created_by, connection),
+        }
+    }
+
+    pub fn rename(id: &Uuid, name: &str, connection: &PgConnection) -> QueryResult<VocabBook> {
+        diesel::update(vocab_books::table.find(id))
+            .set(vocab_books::name.eq(name))
+            .get_result(connection)
+    }
+
+    pub fn delete(id: &Uuid, connection: &PgConnection) -> QueryResult<usize> {
+        connection.transaction(|| {
+            diesel::delete(vocab_book_contents::table.filter(vocab_book_contents::book_id.eq(id)))
+                .execute(connection)?;
+            diesel::delete(vocab_books::table.find(id)).execute(connection)
+        })
+    }
+}
+
+type WordsOfBook = dsl::Filter<
+    dsl::Select<vocab_book_contents::table, vocab_book_contents::vocab>,
+    dsl::Eq<vocab_book_contents::book_id, Uuid>,
+>;
+
+impl VocabBookContent {
+    /// Subquery of the words in a book, to be used with `eq_any`.
+    pub fn words_of(book_id: &Uuid) -> WordsOfBook {
+        vocab_book_contents::table
+            .select(vocab_book_contents::vocab)
+            .filter(vocab_book_contents::book_id.eq(*book_id))
+    }
+
+    pub fn get_by_book(book_id: &Uuid, connection: &PgConnection) -> QueryResult<Vec<VocabBookContent>> {
+        vocab_book_contents::table
+            .filter(vocab_book_contents::book_id.eq(book_id))
+            .order(vocab_book_contents::vocab.asc())
+            .get_results(connection)
+    }
+
+    pub fn count_by_book(book_id: &Uuid, connection: &PgConnection) -> QueryResult<i64> {
+        vocab_book_contents::table
+            .filter(vocab_book_contents::book_id.eq(book_id))
+            .count()
+            .get_result(connection)
+    }
+
+    /// Adds the word unless it is already in the book.
+    pub fn insert(book_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::insert_into(vocab_book_contents::table)
+            .values(&NewVocabBookContent { book_id, vocab })
+            .on_conflict((vocab_book_contents::book_id, vocab_book_contents::vocab))
+            .do_nothing()
+            .execute(connection)
+    }
+
+    pub fn delete(book_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(
+            vocab_book_contents::table
+                .filter(vocab_book_contents::book_id.eq(book_id))
+                .filter(vocab_book_contents::vocab.eq(vocab)),
+        )
+        .execute(connection)
+    }
+}
+
+impl VocabProgress {
+    pub fn get(
+        user_id: &Uuid,
+        vocab: &str,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<VocabProgress>> {
+        vocab_progresses::table
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .filter(vocab_progresses::vocab.eq(vocab))
+            .first::<VocabProgress>(connection)
+            .optional()
+    }
+
+    pub fn count_for_user(user_id: &Uuid, connection: &PgConnection) -> QueryResult<i64> {
+        vocab_progresses::table
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .count()
+            .get_result(connection)
+    }
+
+    /// Words reviewed again `min_interval_days` or more after the last time.
+    pub fn count_mature(user_id: &Uuid, min_interval_days: i32, connection: &PgConnection) -> QueryResult<i64> {
+        vocab_progresses::table
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .filter(vocab_progresses::interval_days.ge(min_interval_days))
+            .count()
+            .get_result(connection)
+    }
+
+    /// When the user's words are due, those due before `until`.
+    pub fn due_before(
+        user_id: &Uuid,
+        until: DateTime<Utc>,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<DateTime<Utc>>> {
+        vocab_progresses::table
+            .select(vocab_progresses::due_at)
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .filter(vocab_progresses::due_at.lt(until))
+            .load(connection)
+    }
+
+    pub fn count_due(user_id: &Uuid, now: DateTime<Utc>, connection: &PgConnection) -> QueryResult<i64> {
+        vocab_progresses::table
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .filter(vocab_progresses::due_at.le(now))
+            .count()
+            .get_result(connection)
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        Schedule {
+            ease_factor: self.ease_factor,
+            interval_days: self.interval_days,
+            repetitions: self.repetitions,
+            lapses: self.lapses,
+            due_at: self.due_at,
+        }
+    }
+
+    /// The word the user should study next: the most overdue review first,
+    /// then a word never seen, and once everything is learned the earliest upcoming review.
+    /// Only words in `scope` are considered, with `with_speech` only words with a recording.
+    pub fn next_due(
+        user_id: &Uuid,
+        scope: WordScope,
+        with_speech: bool,
+        now: DateTime<Utc>,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<String>> {
+        let mut due = vocab_progresses::table
+            .select(vocab_progresses::vocab)
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .filter(vocab_progresses::due_at.le(now))
+            .order(vocab_progresses::due_at.asc())
+            .into_boxed();
+        due = filter_scope!(due, vocab_progresses::vocab, scope);
+        if with_speech {
+            due = due.filter(vocab_progresses::vocab.eq_any(VocabSpeech::words()));
+        }
+        let due = due.first::<String>(connection).optional()?;
+        if due.is_some() {
+            return Ok(due);
+        }
+
+        let seen = vocab_progresses::table
+            .select(vocab_progresses::vocab)
+            .filter(vocab_progresses::user_id.eq(user_id));
+        let mut unseen = vocabs::table
+            .select(vocabs::vocab)
+            .filter(vocabs::vocab.ne_all(seen))
+            .order(vocabs::created_at.desc())
+            .into_boxed();
+        unseen = filter_scope!(unseen, vocabs::vocab, scope);
+        if with_speech {
+            unseen = unseen.filter(vocabs::vocab.eq_any(VocabSpeech::words()));
+        }
+        let unseen = unseen.first::<String>(connection).optional()?;
+        if unseen.is_some() {
+            return Ok(unseen);
+        }
+
+        let mut ahead = vocab_progresses::table
+            .select(vocab_progresses::vocab)
+            .filter(vocab_progresses::user_id.eq(user_id))
+            .order(vocab_progresses::due_at.asc())
+            .into_boxed();
+        ahead = filter_scope!(ahead, vocab_progresses::vocab, scope);
+        if with_speech {
+            ahead = ahead.filter(vocab_progresses::vocab.eq_any(VocabSpeech::words()));
+        }
+        ahead.first::<String>(connection).optional()
+    }
+
+    pub fn review(
+        user_id: &Uuid,
+        vocab: &str,
+        grade: Grade,
+        now: DateTime<Utc>,
+        connection: &PgConnection,
+    ) -> QueryResult<VocabProgress> {
+        let schedule = VocabProgress::get(user_id, vocab, connection)?
+            .map_or_else(|| Schedule::new(now), |p| p.schedule())
+            .review(grade, now);
+        let progress = NewVocabProgress {
+            user_id,
+            vocab,
+            ease_factor: schedule.ease_factor,
+            interval_days: schedule.interval_days,
+            repetitions: schedule.repetitions,
+            lapses: schedule.lapses,
+            due_at: schedule.due_at,
+            reviewed_at: now,
+        };
+        diesel::insert_into(vocab_progresses::table)
+            .values(&progress)
+            .on_conflict((vocab_progresses::user_id, vocab_progresses::vocab))
+            .do_update()
+            .set(&progress)
+            .get_result(connection)
     }
 }
 
+impl StudySession {
+    /// The user's session, started the first time it is needed.
+    pub fn get_or_start(user_id: &Uuid, connection: &PgConnection) -> QueryResult<StudySession> {
+        diesel::insert_into(study_sessions::table)
+            .values(study_sessions::user_id.eq(user_id))
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+        study_sessions::table.find(user_id).get_result(connection)
+    }
+
+    /// The session, locked until the transaction ends so two presses cannot both move it along.
+    pub fn lock(user_id: &Uuid, connection: &PgConnection) -> QueryResult<StudySession> {
+        StudySession::get_or_start(user_id, connection)?;
+        study_sessions::table
+            .find(user_id)
+            .for_update()
+            .get_result(connection)
+    }
+
+    /// A state the session cannot be in is read as answering.
+    pub fn card_state(&self) -> CardState {
+        self.state.parse().unwrap_or(CardState::Answering)
+    }
+
+    pub fn study_mode(&self) -> StudyMode {
+        self.mode.parse().unwrap_or(StudyMode::Flashcard)
+    }
+
+    pub fn latency_ms(&self, now: DateTime<Utc>) -> i32 {
+        let elapsed = (now - self.shown_at).num_milliseconds();
+        elapsed.max(0).min(i32::MAX as i64) as i32
+    }
+
+    /// Shows `vocab` in `state` from `now` on, `None` to pick a new word on the next page.
+    pub fn update(
+        user_id: &Uuid,
+        state: CardState,
+        vocab: Option<&str>,
+        now: DateTime<Utc>,
+        connection: &PgConnection,
+    ) -> QueryResult<StudySession> {
+        diesel::update(study_sessions::table.find(user_id))
+            .set((
+                study_sessions::state.eq(state.as_str()),
+                study_sessions::vocab.eq(vocab),
+                study_sessions::shown_at.eq(now),
+                study_sessions::answer.eq(None::<&str>),
+                study_sessions::token.eq(Uuid::new_v4()),
+                study_sessions::choice_sense.eq(None::<Uuid>),
+                study_sessions::choice_options.eq(None::<Vec<String>>),
+            ))
+            .get_result(connection)
+    }
+
+    /// Keeps the multiple choice question asked for the current card.
+    pub fn ask(
+        user_id: &Uuid,
+        sense_id: &Uuid,
+        by_word: bool,
+        options: &[String],
+        connection: &PgConnection,
+    ) -> QueryResult<StudySession> {
+        diesel::update(study_sessions::table.find(user_id))
+            .set((
+                study_sessions::choice_sense.eq(sense_id),
+                study_sessions::choice_by_word.eq(by_word),
+                study_sessions::choice_options.eq(options),
+            ))
+            .get_result(connection)
+    }
+
+    /// Starts timing the card again, e.g. when its page is reloaded.
+    pub fn touch(user_id: &Uuid, now: DateTime<Utc>, connection: &PgConnection) -> QueryResult<StudySession> {
+        diesel::update(study_sessions::table.find(user_id))
+            .set(study_sessions::shown_at.eq(now))
+            .get_result(connection)
+    }
+
+    /// Shows the graded `answer` typed for the current card.
+    pub fn review_answer(
+        user_id: &Uuid,
+        answer: &str,
+        now: DateTime<Utc>,
+        connection: &PgConnection,
+    ) -> QueryResult<StudySession> {
+        diesel::update(study_sessions::table.find(user_id))
+            .set((
+                study_sessions::state.eq(CardState::Reviewing.as_str()),
+                study_sessions::answer.eq(answer),
+                study_sessions::shown_at.eq(now),
+                study_sessions::token.eq(Uuid::new_v4()),
+            ))
+            .get_result(connection)
+    }
+
+    /// Asks in `mode` from now on, a card already revealed still has to be graded first.
+    pub fn set_mode(user_id: &Uuid, mode: StudyMode, connection: &PgConnection) -> QueryResult<StudySession> {
+        StudySession::get_or_start(user_id, connection)?;
+        diesel::update(study_sessions::table.find(user_id))
+            .set((
+                study_sessions::mode.eq(mode.as_str()),
+                study_sessions::token.eq(Uuid::new_v4()),
+            ))
+            .get_result(connection)
+    }
+
+    /// Studies the words of `book_id`, or every word, starting with a new card.
+    pub fn choose_book(
+        user_id: &Uuid,
+        book_id: Option<&Uuid>,
+        connection: &PgConnection,
+    ) -> QueryResult<StudySession> {
+        StudySession::choose(user_id, book_id, false, connection)
+    }
+
+    /// Studies the words the user liked, starting with a new card.
+    pub fn choose_favorites(user_id: &Uuid, connection: &PgConnection) -> QueryResult<StudySession> {
+        StudySession::choose(user_id, None, true, connection)
+    }
+
+    fn choose(
+        user_id: &Uuid,
+        book_id: Option<&Uuid>,
+        favorites: bool,
+        connection: &PgConnection,
+    ) -> QueryResult<StudySession> {
+        StudySession::get_or_start(user_id, connection)?;
+        diesel::update(study_sessions::table.find(user_id))
+            .set((
+                study_sessions::book_id.eq(book_id),
+                study_sessions::favorites.eq(favorites),
+                study_sessions::state.eq(CardState::Answering.as_str()),
+                study_sessions::vocab.eq(None::<&str>),
+                study_sessions::answer.eq(None::<&str>),
+                study_sessions::token.eq(Uuid::new_v4()),
+                study_sessions::choice_sense.eq(None::<Uuid>),
+                study_sessions::choice_options.eq(None::<Vec<String>>),
+            ))
+            .get_result(connection)
+    }
+}
+
+impl ReviewLog {
+    /// The user's last `limit` reviews of `vocab`, latest first.
+    pub fn get_by_word(
+        user_id: &Uuid,
+        vocab: &str,
+        limit: i64,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<ReviewLog>> {
+        review_logs::table
+            .filter(review_logs::user_id.eq(user_id))
+            .filter(review_logs::vocab.eq(vocab))
+            .order(review_logs::created_at.desc())
+            .limit(limit)
+            .get_results(connection)
+    }
+
+    /// `grade` is the grade the card got when the action finished it.
+    pub fn insert(
+        user_id: &Uuid,
+        vocab: &str,
+        action: ReviewAction,
+        grade: Option<Grade>,
+        latency_ms: i32,
+        connection: &PgConnection,
+    ) -> QueryResult<ReviewLog> {
+        let log = NewReviewLog {
+            user_id,
+            vocab,
+            action: action.into(),
+            latency_ms,
+            passed: grade.map(Grade::is_pass),
+        };
+        diesel::insert_into(review_logs::table)
+            .values(&log)
+            .get_result(connection)
+    }
+
+    /// When each card was finished since `since`.
+    pub fn finished_since(
+        user_id: &Uuid,
+        since: DateTime<Utc>,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<DateTime<Utc>>> {
+        review_logs::table
+            .select(review_logs::created_at)
+            .filter(review_logs::user_id.eq(user_id))
+            .filter(review_logs::passed.is_not_null())
+            .filter(review_logs::created_at.ge(since))
+            .load(connection)
+    }
+
+    /// The UTC days with a finished card, latest first.
+    pub fn days(user_id: &Uuid, connection: &PgConnection) -> QueryResult<Vec<NaiveDate>> {
+        review_logs::table
+            .select(dsl::sql::<sql_types::Date>("(created_at AT TIME ZONE 'UTC')::date AS day"))
+            .filter(review_logs::user_id.eq(user_id))
+            .filter(review_logs::passed.is_not_null())
+            .distinct()
+            .order(dsl::sql::<sql_types::Date>("day").desc())
+            .load(connection)
+    }
+
+    /// How many finished cards of words in `scope` passed, out of how many.
+    pub fn accuracy(
+        user_id: &Uuid,
+        scope: WordScope,
+        connection: &PgConnection,
+    ) -> QueryResult<(i64, i64)> {
+        let finished = || {
+            let query = review_logs::table
+                .filter(review_logs::user_id.eq(user_id))
+                .filter(review_logs::passed.is_not_null())
+                .into_boxed();
+            filter_scope!(query, review_logs::vocab, scope)
+        };
+        let passed = finished().filter(review_logs::passed.eq(true)).count().get_result(connection)?;
+        let total = finished().count().get_result(connection)?;
+        Ok((passed, total))
+    }
+
+    /// `accuracy` of each of the user's books with a finished card, by book id.
+    pub fn accuracy_by_book(
+        user_id: &Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<std::collections::HashMap<Uuid, (i64, i64)>> {
+        let books = vocab_books::table
+            .select(vocab_books::id)
+            .filter(vocab_books::created_by.eq(user_id));
+        let counts = review_logs::table
+            .inner_join(vocab_book_contents::table.on(vocab_book_contents::vocab.eq(review_logs::vocab)))
+            .select((
+                vocab_book_contents::book_id,
+                dsl::sql::<sql_types::BigInt>("count(*) FILTER (WHERE review_logs.passed)"),
+                dsl::count_star(),
+            ))
+            .filter(review_logs::user_id.eq(user_id))
+            .filter(review_logs::passed.is_not_null())
+            .filter(vocab_book_contents::book_id.eq_any(books))
+            .group_by(vocab_book_contents::book_id)
+            .load::<(Uuid, i64, i64)>(connection)?;
+        Ok(counts.into_iter().map(|(book_id, passed, total)| (book_id, (passed, total))).collect())
+    }
+}
+
+type LikedWords = dsl::Filter<
+    dsl::Select<vocab_likes::table, vocab_likes::vocab>,
+    dsl::Eq<vocab_likes::user_id, Uuid>,
+>;
+
+impl VocabExample {
+    /// Examples of every sense of `vocab`, oldest first.
+    pub fn get_by_word(vocab: &str, connection: &PgConnection) -> QueryResult<Vec<VocabExample>> {
+        let senses = vocab_dicts::table
+            .select(vocab_dicts::id)
+            .filter(vocab_dicts::vocab.eq(vocab));
+        vocab_examples::table
+            .filter(vocab_examples::dict_id.eq_any(senses))
+            .order(vocab_examples::created_at.asc())
+            .get_results(connection)
+    }
+
+    pub fn get(id: &Uuid, connection: &PgConnection) -> QueryResult<Option<VocabExample>> {
+        vocab_examples::table.find(id).get_result(connection).optional()
+    }
+
+    /// Returns whether the example was new for its sense.
+    pub fn insert(example: &NewVocabExample, connection: &PgConnection) -> QueryResult<bool> {
+        let inserted = diesel::insert_into(vocab_examples::table)
+            .values(example)
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+        Ok(inserted > 0)
+    }
+
+    pub fn delete(id: &Uuid, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(vocab_examples::table.find(id)).execute(connection)
+    }
+}
+
+impl VocabNote {
+    pub fn get(user_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<Option<VocabNote>> {
+        vocab_notes::table.find((user_id, vocab)).get_result(connection).optional()
+    }
+
+    /// Writes the user's note of the word, replacing the one there was.
+    pub fn save(note: &NewVocabNote, connection: &PgConnection) -> QueryResult<VocabNote> {
+        diesel::insert_into(vocab_notes::table)
+            .values(note)
+            .on_conflict((vocab_notes::user_id, vocab_notes::vocab))
+            .do_update()
+            .set(vocab_notes::body.eq(note.body))
+            .get_result(connection)
+    }
+
+    pub fn delete(user_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(vocab_notes::table.find((user_id, vocab))).execute(connection)
+    }
+}
+
+impl VocabLike {
+    /// Subquery of the words a user liked, to be used with `eq_any`.
+    pub fn words_of(user_id: &Uuid) -> LikedWords {
+        vocab_likes::table
+            .select(vocab_likes::vocab)
+            .filter(vocab_likes::user_id.eq(*user_id))
+    }
+
+    /// Latest first.
+    pub fn get_by_user(user_id: &Uuid, connection: &PgConnection) -> QueryResult<Vec<VocabLike>> {
+        vocab_likes::table
+            .filter(vocab_likes::user_id.eq(user_id))
+            .order(vocab_likes::created_at.desc())
+            .get_results(connection)
+    }
+
+    pub fn count_by_user(user_id: &Uuid, connection: &PgConnection) -> QueryResult<i64> {
+        vocab_likes::table
+            .filter(vocab_likes::user_id.eq(user_id))
+            .count()
+            .get_result(connection)
+    }
+
+    pub fn exists(user_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(vocab_likes::table.find((user_id, vocab)))).get_result(connection)
+    }
+
+    /// Those of `words` the user liked.
+    pub fn liked_among(user_id: &Uuid, words: &[String], connection: &PgConnection) -> QueryResult<Vec<String>> {
+        VocabLike::words_of(user_id)
+            .filter(vocab_likes::vocab.eq_any(words))
+            .load(connection)
+    }
+
+    /// Likes the word, `false` if it already was.
+    pub fn insert(user_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<bool> {
+        connection.transaction(|| {
+            let inserted = diesel::insert_into(vocab_likes::table)
+                .values(&NewVocabLike { user_id, vocab })
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+            if inserted > 0 {
+                diesel::update(vocabs::table.find(vocab))
+                    .set(vocabs::like_count.eq(vocabs::like_count + 1))
+                    .execute(connection)?;
+            }
+            Ok(inserted > 0)
+        })
+    }
+
+    /// Unlikes the word, `false` if it was not liked.
+    pub fn delete(user_id: &Uuid, vocab: &str, connection: &PgConnection) -> QueryResult<bool> {
+        connection.transaction(|| {
+            let deleted = diesel::delete(vocab_likes::table.find((user_id, vocab))).execute(connection)?;
+            if deleted > 0 {
+                diesel::update(vocabs::table.find(vocab))
+                    .set(vocabs::like_count.eq(vocabs::like_count - 1))
+                    .execute(connection)?;
+            }
+            Ok(deleted > 0)
+        })
+    }
+}
+
+type WordsWithSpeech = dsl::Select<vocab_speeches::table, vocab_speeches::vocab>;
+
 impl VocabSpeech {
+    /// Subquery of the words with a recording, to be used with `eq_any`.
+    pub fn words() -> WordsWithSpeech {
+        vocab_speeches::table.select(vocab_speeches::vocab)
+    }
+
     pub fn get_by_word(word: &String, connection: &PgConnection) -> QueryResult<VocabSpeech> {
         let query = vocab_speeches::table.filter(vocab_speeches::vocab.eq(word));
         query.get_result::<VocabSpeech>(connection)
     }
-}
 
-    // impl Password {
-    //     pub fn get_match(
-    //         user_id: &Uuid,
-    //         passwd: &str,
-    //         connection: &PgConnection,
-    //     ) -> QueryResult<Password> {
-    //         let query = diesel::sql_query(format!(
-    //             " SELECT * FROM passwords
-    //               WHERE user_id = '{}' AND passwd = crypt('{}', passwd);
-    //             ",
-    //             user_id, passwd
-    //         ));
-    //         // println!("{}", diesel::debug_query(&query));
-    //         query.get_result::<Password>(connection)
-    //     }
-    // }
+    pub fn exists(word: &str, connection: &PgConnection) -> QueryResult<bool> {
+        let recorded = vocab_speeches::table.filter(vocab_speeches::vocab.eq(word));
+        diesel::select(dsl::exists(recorded)).get_result(connection)
+    }
+
+    /// Keeps the recording a word already has, returns whether this one was stored.
+    pub fn insert_if_missing(speech: &NewVocabSpeech, connection: &PgConnection) -> QueryResult<bool> {
+        let inserted = diesel::insert_into(vocab_speeches::table)
+            .values(speech)
+            .on_conflict(vocab_speeches::vocab)
+            .do_nothing()
+            .execute(connection)?;
+        Ok(inserted > 0)
+    }
+
+    /// Returns whether a recording was replaced.
+    pub fn upsert(speech: &NewVocabSpeech, connection: &PgConnection) -> QueryResult<bool> {
+        let recorded = vocab_speeches::table.filter(vocab_speeches::vocab.eq(speech.vocab));
+        let replaced = diesel::select(dsl::exists(recorded)).get_result(connection)?;
+        diesel::insert_into(vocab_speeches::table)
+            .values(speech)
+            .on_conflict(vocab_speeches::vocab)
+            .do_update()
+            .set(speech)
+            .execute(connection)?;
+        Ok(replaced)
+    }
+}
 
     // impl Manager {
     //     pub fn all(connection: &PgConnection) -> QueryResult<Vec<Manager>> {
@@ -232,60 +1373,3 @@ impl VocabSpeech {
     //         diesel::delete(reviews::table.find(id)).execute(connection)
     //     }
     // }
-
-    // impl User {
-    //     pub fn all(connection: &PgConnection) -> QueryResult<Vec<User>> {
-    //         users::table.load::<User>(&*connection)
-    //     }
-
-    //     pub fn get(id: &Uuid, connection: &PgConnection) -> QueryResult<User> {
-    //         let query = users::table.find(id);
-    //         // println!("{}", diesel::debug_query(&query));
-    //         query.get_result::<User>(connection)
-    //     }
-
-    //     pub fn get_by_username(name: &str, connection: &PgConnection) -> QueryResult<User> {
-    //         let query = users::table.filter(users::username.eq(name));
-    //         // println!("{}", diesel::debug_query(&query));
-    //         query.get_result::<User>(connection)
-    //     }
-
-    //     pub fn get_by_email(email: &str, connection: &PgConnection) -> QueryResult<User> {
-    //         let query = users::table.filter(users::email.eq(email));
-    //         // println!("{}", diesel::debug_query(&query));
-    //         query.get_result::<User>(connection)
-    //     }
-
-    //     pub fn insert_by_email(
-    //         email: &str,
-    //         passwd: &str,
-    //         connection: &PgConnection,
-    //     ) -> QueryResult<usize> {
-    //         let query = diesel::sql_query(format!(
-    //             r#"
-    //              WITH user_insert AS (
-    //                 INSERT INTO users (username, email, firstname, lastname)
-    //                 VALUES ('', '{email}', '', '')
-    //                 RETURNING id
-    //              )
-    //              INSERT INTO passwords (user_id, passwd)
-    //                SELECT id, crypt('{password}', gen_salt('bf'))
-    //                FROM user_insert;
-    //              "#,
-    //             email = email,
-    //             password = passwd
-    //         ));
-    //         query.execute(connection)
-    //     }
-
-    //     pub fn update(id: &Uuid, user: &User, connection: &PgConnection) -> QueryResult<User> {
-    //         diesel::update(users::table.find(id))
-    //             .set(user)
-    //             .get_result(connection)
-    //     }
-
-    //     pub fn delete(id: &Uuid, connection: &PgConnection) -> QueryResult<usize> {
-    //         diesel::delete(users::table.find(id)).execute(connection)
-    //     }
-    // }
-
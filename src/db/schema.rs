@@ -1,3 +1,55 @@
+table! {
+    api_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        token_hash -> Bytea,
+        last_used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    passwords (user_id) {
+        user_id -> Uuid,
+        passwd -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    review_logs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        vocab -> Varchar,
+        action -> Varchar,
+        latency_ms -> Int4,
+        created_at -> Timestamptz,
+        passed -> Nullable<Bool>,
+    }
+}
+
+table! {
+    study_sessions (user_id) {
+        user_id -> Uuid,
+        state -> Varchar,
+        vocab -> Nullable<Varchar>,
+        book_id -> Nullable<Uuid>,
+        shown_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        mode -> Varchar,
+        answer -> Nullable<Varchar>,
+        favorites -> Bool,
+        token -> Uuid,
+        choice_sense -> Nullable<Uuid>,
+        choice_by_word -> Bool,
+        choice_options -> Nullable<Array<Varchar>>,
+    }
+}
+
 table! {
     users (id) {
         id -> Uuid,
@@ -5,6 +57,8 @@ table! {
         email -> Varchar,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        email_verified_at -> Nullable<Timestamptz>,
+        is_admin -> Bool,
     }
 }
 
@@ -39,6 +93,51 @@ table! {
     }
 }
 
+table! {
+    vocab_examples (id) {
+        id -> Uuid,
+        dict_id -> Uuid,
+        body -> Text,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    vocab_likes (user_id, vocab) {
+        user_id -> Uuid,
+        vocab -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    vocab_notes (user_id, vocab) {
+        user_id -> Uuid,
+        vocab -> Varchar,
+        body -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    vocab_progresses (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        vocab -> Varchar,
+        ease_factor -> Float8,
+        interval_days -> Int4,
+        repetitions -> Int4,
+        lapses -> Int4,
+        due_at -> Timestamptz,
+        reviewed_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 table! {
     vocab_speeches (id) {
         id -> Uuid,
@@ -46,6 +145,7 @@ table! {
         updated_at -> Timestamptz,
         vocab -> Varchar,
         mp3 -> Bytea,
+        format -> Varchar,
     }
 }
 
@@ -54,20 +154,46 @@ table! {
         vocab -> Varchar,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        review_count -> Int4,
+        like_count -> Int4,
     }
 }
 
+joinable!(api_tokens -> users (user_id));
+joinable!(passwords -> users (user_id));
+joinable!(review_logs -> users (user_id));
+joinable!(review_logs -> vocabs (vocab));
+joinable!(study_sessions -> users (user_id));
+joinable!(study_sessions -> vocab_books (book_id));
+joinable!(study_sessions -> vocab_dicts (choice_sense));
+joinable!(study_sessions -> vocabs (vocab));
 joinable!(vocab_book_contents -> vocab_books (book_id));
 joinable!(vocab_book_contents -> vocabs (vocab));
 joinable!(vocab_books -> users (created_by));
 joinable!(vocab_dicts -> vocabs (vocab));
+joinable!(vocab_examples -> users (created_by));
+joinable!(vocab_examples -> vocab_dicts (dict_id));
+joinable!(vocab_likes -> users (user_id));
+joinable!(vocab_likes -> vocabs (vocab));
+joinable!(vocab_notes -> users (user_id));
+joinable!(vocab_notes -> vocabs (vocab));
+joinable!(vocab_progresses -> users (user_id));
+joinable!(vocab_progresses -> vocabs (vocab));
 joinable!(vocab_speeches -> vocabs (vocab));
 
 allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    passwords,
+    review_logs,
+    study_sessions,
     users,
     vocab_book_contents,
     vocab_books,
     vocab_dicts,
+    vocab_examples,
+    vocab_likes,
+    vocab_notes,
+    vocab_progresses,
     vocab_speeches,
     vocabs,
 );
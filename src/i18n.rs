@@ -0,0 +1,73 @@
+// The catalog of user-facing strings, one `key = "text"` file per locale under locales/.
+// English is the reference, a key missing from another locale shows its English text.
+use std::{collections::HashMap, fmt::Display};
+
+use crate::types::ServerAcceptLangauge;
+
+use anyhow::{Context, Result};
+
+pub type Strings = HashMap<&'static str, &'static str>;
+
+pub const ENGLISH: &str = include_str!("../locales/en.toml");
+
+pub const LOCALES: &[(ServerAcceptLangauge, &str)] = &[
+    (
+        ServerAcceptLangauge::SimpliedChinese,
+        include_str!("../locales/zh.toml"),
+    ),
+    (
+        ServerAcceptLangauge::Japananese,
+        include_str!("../locales/ja.toml"),
+    ),
+];
+
+lazy_static! {
+    pub static ref TEXT: HashMap<ServerAcceptLangauge, Strings> =
+        load().expect("invalid locale file");
+}
+
+/// Parses a locale file, the strings live as long as the server.
+pub fn parse(source: &str) -> Result<Strings> {
+    let table: HashMap<String, String> = toml::from_str(source)?;
+    Ok(table
+        .into_iter()
+        .map(|(key, text)| -> (&'static str, &'static str) {
+            (
+                Box::leak(key.into_boxed_str()),
+                Box::leak(text.into_boxed_str()),
+            )
+        })
+        .collect())
+}
+
+/// Fills the keys missing from `strings` with the ones of `fallback`.
+pub fn with_fallback(mut strings: Strings, fallback: &Strings) -> Strings {
+    for (key, text) in fallback {
+        strings.entry(*key).or_insert(*text);
+    }
+    strings
+}
+
+/// `text` with each `{}` replaced by the next of `args`, the text decides where they go.
+pub fn fill(text: &str, args: &[&dyn Display]) -> String {
+    let mut parts = text.split("{}");
+    let mut filled = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            filled.push_str(&arg.to_string());
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+fn load() -> Result<HashMap<ServerAcceptLangauge, Strings>> {
+    let english = parse(ENGLISH).context("locales/en.toml")?;
+    let mut catalog = HashMap::new();
+    for (lang, source) in LOCALES {
+        let strings = parse(source).with_context(|| format!("the {} locale", lang))?;
+        catalog.insert(*lang, with_fallback(strings, &english));
+    }
+    catalog.insert(ServerAcceptLangauge::English, english);
+    Ok(catalog)
+}
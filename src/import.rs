@@ -0,0 +1,494 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use rocket::{
+    data::Data,
+    http::{RawStr, Status},
+    request::FromFormValue,
+};
+use rocket_contrib::databases::diesel::{Connection, PgConnection};
+
+use anyhow::{anyhow, Context, Result};
+use maud::{html, Markup};
+use serde::Deserialize;
+use strum::{EnumString, IntoStaticStr};
+use uuid::Uuid;
+
+use crate::{
+    account::Admin, app_page_view, audio::Rejection, db, db::*, i18n::fill, types::ServerAcceptLangauge, TEXT,
+};
+
+// uploads bigger than this are refused with 413
+const MAX_UPLOAD_BYTES: u64 = 16 * 1024 * 1024;
+const MAX_WORD_LEN: usize = 100;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumString, IntoStaticStr)]
+pub enum ImportFormat {
+    #[strum(serialize = "csv")]
+    Csv,
+    #[strum(serialize = "tsv")]
+    Tsv,
+    #[strum(serialize = "json")]
+    Json,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<ImportFormat> {
+        path.extension()?.to_str()?.to_lowercase().parse().ok()
+    }
+}
+
+impl<'v> FromFormValue<'v> for ImportFormat {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        ImportFormat::from_str(form_value.as_str()).map_err(|_| form_value)
+    }
+}
+
+/// One sense of a word, csv/tsv files need a header row naming these columns.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ImportRow {
+    pub word: String,
+    #[serde(alias = "pos")]
+    pub partofspeech: String,
+    pub meaning: String,
+    #[serde(default)]
+    pub book: Option<String>,
+    /// Markdown, added to the sense
+    #[serde(default)]
+    pub example: Option<String>,
+}
+
+impl ImportRow {
+    pub fn validate(mut self) -> Result<ImportRow, RowProblem> {
+        self.word = self.word.trim().to_string();
+        self.partofspeech = self.partofspeech.trim().to_string();
+        self.meaning = self.meaning.trim().to_string();
+        self.book = self
+            .book
+            .map(|b| b.trim().to_string())
+            .filter(|b| !b.is_empty());
+        self.example = self
+            .example
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty());
+        if self.word.is_empty() {
+            Err(RowProblem::WordEmpty)
+        } else if self.word.chars().count() > MAX_WORD_LEN {
+            Err(RowProblem::WordTooLong)
+        } else if self.partofspeech.is_empty() {
+            Err(RowProblem::PartOfSpeechEmpty)
+        } else if self.meaning.is_empty() {
+            Err(RowProblem::MeaningEmpty)
+        } else if self.example.as_ref().map_or(false, |e| e.chars().count() > crate::words::MAX_EXAMPLE_LEN) {
+            Err(RowProblem::ExampleTooLong)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// Why a row was not imported, worded in the language of the page and in English on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowProblem {
+    WordEmpty,
+    WordTooLong,
+    PartOfSpeechEmpty,
+    MeaningEmpty,
+    ExampleTooLong,
+    NoOwner,
+    NotJsonArray(String),
+    /// GBK or UTF-16 exports, say
+    NotUtf8,
+    /// the recording that came with the word in an Anki deck
+    Audio(String, Rejection),
+    NotApkg,
+    DeckTooBig,
+    DeckNeedsNewerAnki,
+    DeckWithoutCollection,
+    /// as the csv or json reader put it
+    Unreadable(String),
+}
+
+impl RowProblem {
+    pub fn text(&self, lang: &ServerAcceptLangauge) -> String {
+        let text = &TEXT[lang];
+        match self {
+            RowProblem::WordEmpty => text["import-word-empty"].to_string(),
+            RowProblem::WordTooLong => fill(text["import-word-too-long"], &[&MAX_WORD_LEN]),
+            RowProblem::PartOfSpeechEmpty => text["import-partofspeech-empty"].to_string(),
+            RowProblem::MeaningEmpty => text["import-meaning-empty"].to_string(),
+            RowProblem::ExampleTooLong => fill(text["import-example-too-long"], &[&crate::words::MAX_EXAMPLE_LEN]),
+            RowProblem::NoOwner => text["import-no-owner"].to_string(),
+            RowProblem::NotJsonArray(e) => fill(text["import-not-json-array"], &[e]),
+            RowProblem::NotUtf8 => text["import-not-utf8"].to_string(),
+            RowProblem::Audio(word, reason) => format!("{}: {}", word, reason.text(lang)),
+            RowProblem::NotApkg => text["anki-not-apkg"].to_string(),
+            RowProblem::DeckTooBig => fill(text["anki-too-big"], &[&(crate::anki::MAX_UNPACKED_BYTES >> 20)]),
+            RowProblem::DeckNeedsNewerAnki => text["anki-needs-newer"].to_string(),
+            RowProblem::DeckWithoutCollection => text["anki-no-collection"].to_string(),
+            RowProblem::Unreadable(e) => e.clone(),
+        }
+    }
+}
+
+impl fmt::Display for RowProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text(&ServerAcceptLangauge::English))
+    }
+}
+
+/// A row that could not be imported, `line` is the line in csv/tsv or the entry in json, from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub line: u64,
+    pub problem: RowProblem,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub new_words: usize,
+    pub new_senses: usize,
+    pub book_entries: usize,
+    pub new_audio: usize,
+    pub new_examples: usize,
+    /// senses already in the dictionary, by line and word
+    pub duplicates: Vec<(u64, String)>,
+    pub failed: Vec<RowError>,
+}
+
+/// The upload as text, or the line of the first byte that is not UTF-8.
+pub fn decode(input: Vec<u8>) -> Result<String, RowError> {
+    String::from_utf8(input).map_err(|e| {
+        let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+        RowError {
+            line: valid.iter().filter(|&&b| b == b'\n').count() as u64 + 1,
+            problem: RowProblem::NotUtf8,
+        }
+    })
+}
+
+/// Parses every row it can, collecting the others as errors.
+pub fn parse(format: ImportFormat, input: &str) -> (Vec<(u64, ImportRow)>, Vec<RowError>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut push = |line: u64, row: Result<ImportRow, RowProblem>| match row.and_then(ImportRow::validate) {
+        Ok(row) => rows.push((line, row)),
+        Err(problem) => errors.push(RowError { line, problem }),
+    };
+
+    match format {
+        ImportFormat::Csv | ImportFormat::Tsv => {
+            let delimiter = if format == ImportFormat::Tsv { b'\t' } else { b',' };
+            let mut reader = csv::ReaderBuilder::new()
+                .delimiter(delimiter)
+                .flexible(true)
+                .trim(csv::Trim::All)
+                .from_reader(input.as_bytes());
+            match reader.headers().map(|h| h.clone()) {
+                Ok(headers) => {
+                    for record in reader.records() {
+                        match record {
+                            Ok(record) => {
+                                let line = record.position().map_or(0, |p| p.line());
+                                let row: Result<ImportRow, _> = record.deserialize(Some(&headers));
+                                push(line, row.map_err(|e| RowProblem::Unreadable(e.to_string())));
+                            }
+                            Err(e) => {
+                                let line = e.position().map_or(0, |p| p.line());
+                                push(line, Err(RowProblem::Unreadable(e.to_string())))
+                            }
+                        }
+                    }
+                }
+                Err(e) => push(1, Err(RowProblem::Unreadable(e.to_string()))),
+            }
+        }
+        ImportFormat::Json => match serde_json::from_str::<Vec<serde_json::Value>>(input) {
+            Ok(values) => {
+                for (i, value) in values.into_iter().enumerate() {
+                    let row: Result<ImportRow, _> = serde_json::from_value(value);
+                    push(i as u64 + 1, row.map_err(|e| RowProblem::Unreadable(e.to_string())));
+                }
+            }
+            Err(e) => push(e.line() as u64, Err(RowProblem::NotJsonArray(e.to_string()))),
+        },
+    }
+    (rows, errors)
+}
+
+/// Adds the rows to vocabs, vocab_dicts and, for rows naming a book, to `owner`'s book of that name.
+/// Everything is written in one transaction.
+pub fn import(
+    rows: &[(u64, ImportRow)],
+    owner: Option<&Uuid>,
+    connection: &PgConnection,
+) -> Result<ImportReport> {
+    connection.transaction(|| {
+        let mut report = ImportReport::default();
+        let mut books: HashMap<&str, Uuid> = HashMap::new();
+        for (line, row) in rows {
+            let book_id = match (&row.book, owner) {
+                (Some(name), Some(owner)) => match books.get(name.as_str()) {
+                    Some(id) => Some(*id),
+                    None => {
+                        let book = VocabBook::get_or_insert_by_name(name, owner, connection)?;
+                        books.insert(name.as_str(), book.id);
+                        Some(book.id)
+                    }
+                },
+                (Some(_), None) => {
+                    report.failed.push(RowError {
+                        line: *line,
+                        problem: RowProblem::NoOwner,
+                    });
+                    continue;
+                }
+                (None, _) => None,
+            };
+
+            if Vocab::insert_if_missing(&row.word, connection)? {
+                report.new_words += 1;
+            }
+            let sense = NewVocabDict {
+                vocab: &row.word,
+                partofspeech: &row.partofspeech,
+                meaning: &row.meaning,
+            };
+            if VocabDict::insert_if_missing(&sense, connection)? {
+                report.new_senses += 1;
+            } else {
+                report.duplicates.push((*line, row.word.clone()));
+            }
+            if let Some(body) = &row.example {
+                let example = NewVocabExample {
+                    dict_id: &VocabDict::get_id(&sense, connection)?,
+                    body,
+                    created_by: None,
+                };
+                if VocabExample::insert(&example, connection)? {
+                    report.new_examples += 1;
+                }
+            }
+            if let Some(book_id) = book_id {
+                report.book_entries += VocabBookContent::insert(&book_id, &row.word, connection)?;
+            }
+        }
+        Ok(report)
+    })
+}
+
+/// `beidanci import <file> [--format csv|tsv|json] [--owner <email>]`
+pub fn run_cli(args: &[String]) -> Result<()> {
+    let usage = "usage: beidanci import <file> [--format csv|tsv|json] [--owner <email>]";
+    let mut path = None;
+    let mut format = None;
+    let mut owner_email = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().context(usage)?;
+                format = Some(ImportFormat::from_str(value).map_err(|_| anyhow!("unknown format {}", value))?);
+            }
+            "--owner" => owner_email = Some(args.next().context(usage)?.clone()),
+            _ if path.is_none() => path = Some(Path::new(arg)),
+            _ => return Err(anyhow!(usage)),
+        }
+    }
+    let path = path.context(usage)?;
+    let format = match format {
+        Some(format) => format,
+        None => ImportFormat::from_path(path).context("cannot tell the format, pass --format")?,
+    };
+    let input = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let input = decode(input).map_err(|e| anyhow!("{} line {}: {}", path.display(), e.line, e.problem))?;
+
+    let connection = db::establish()?;
+    let owner = match owner_email {
+        Some(email) => Some(User::get_by_email(&email, &connection)?.with_context(|| format!("no user {}", email))?),
+        None => None,
+    };
+
+    let (rows, errors) = parse(format, &input);
+    let mut report = import(&rows, owner.as_ref().map(|u| &u.id), &connection)?;
+    report.failed.extend(errors);
+    report.failed.sort_by_key(|e| e.line);
+
+    println!(
+        "{} new words, {} new senses, {} new examples, {} book entries",
+        report.new_words, report.new_senses, report.new_examples, report.book_entries
+    );
+    for (line, word) in &report.duplicates {
+        println!("line {}: duplicate sense of {}", line, word);
+    }
+    for error in &report.failed {
+        println!("line {}: {}", error.line, error.problem);
+    }
+    Ok(())
+}
+
+/// Everything read from `input`, `None` when there is more than `limit` bytes of it.
+pub fn read_limited(input: impl Read, limit: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    input.take(limit + 1).read_to_end(&mut bytes)?;
+    Ok(if bytes.len() as u64 > limit { None } else { Some(bytes) })
+}
+
+#[get("/admin/import")]
+pub fn import_page(lang: ServerAcceptLangauge, admin: Admin) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        &admin.0,
+        &None,
+        html! {
+            h1 class="title" { (text["import-title"]) }
+            p class="block" { (text["import-help"]) }
+            form id="import-form" {
+                div class="field is-grouped" {
+                    div class="control" {
+                        div class="select" {
+                            select id="import-format" {
+                                option value="csv" { "CSV" }
+                                option value="tsv" { "TSV" }
+                                option value="json" { "JSON" }
+                            }
+                        }
+                    }
+                    div class="control is-expanded" {
+                        input class="input" type="file" id="import-file" required? {}
+                    }
+                    div class="control" {
+                        button class="button is-primary" type="submit" { (text["import-submit"]) }
+                    }
+                }
+            }
+            (upload_js("import-form", "import-file", "'/admin/import?format=' + document.getElementById('import-format').value"))
+        },
+    )
+}
+
+#[post("/admin/import?<format>", data = "<data>")]
+pub fn import_upload(
+    lang: ServerAcceptLangauge,
+    admin: Admin,
+    format: ImportFormat,
+    data: Data,
+    conn: db::Connection,
+) -> Result<Result<Markup, Status>> {
+    let input = match read_limited(data.open(), MAX_UPLOAD_BYTES)? {
+        Some(input) => decode(input),
+        None => return Ok(Err(Status::PayloadTooLarge)),
+    };
+
+    let mut report = match input {
+        Ok(input) => {
+            let (rows, errors) = parse(format, &input);
+            let mut report = import(&rows, Some(&admin.0.id), &conn)?;
+            report.failed.extend(errors);
+            report
+        }
+        Err(error) => ImportReport {
+            failed: vec![error],
+            ..ImportReport::default()
+        },
+    };
+    report.failed.sort_by_key(|e| e.line);
+    Ok(Ok(import_report_view(lang, &admin.0, "import-title", &uri!(import_page).to_string(), &report)))
+}
+
+/// Posts the chosen file as the raw request body and shows the html answer.
+pub fn upload_js(form_id: &str, file_id: &str, url_js: &str) -> Markup {
+    html! {
+        (maud::PreEscaped(format!(
+        r#"
+          <script type="text/javascript">
+            document.getElementById('{form}').addEventListener('submit', function (event) {{
+                event.preventDefault();
+                var file = document.getElementById('{file}').files[0];
+                fetch({url}, {{ method: 'POST', body: file, credentials: 'same-origin' }})
+                    .then(function (response) {{ return response.text(); }})
+                    .then(function (page) {{ document.open(); document.write(page); document.close(); }});
+            }});
+          </script>
+        "#,
+        form = form_id,
+        file = file_id,
+        url = url_js
+        )))
+    }
+}
+
+/// `title` is a `TEXT` key, `again_url` leads back to the upload form.
+pub fn import_report_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    title: &str,
+    again_url: &str,
+    report: &ImportReport,
+) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        user,
+        &None,
+        html! {
+            h1 class="title" { (text[title]) }
+            nav class="level" {
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["import-new-words"]) } p class="title" { (report.new_words) } }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["import-new-senses"]) } p class="title" { (report.new_senses) } }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["import-book-entries"]) } p class="title" { (report.book_entries) } }
+                }
+                @if report.new_examples > 0 {
+                    div class="level-item has-text-centered" {
+                        div { p class="heading" { (text["import-new-examples"]) } p class="title" { (report.new_examples) } }
+                    }
+                }
+                @if report.new_audio > 0 {
+                    div class="level-item has-text-centered" {
+                        div { p class="heading" { (text["import-new-audio"]) } p class="title" { (report.new_audio) } }
+                    }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["import-duplicates"]) } p class="title" { (report.duplicates.len()) } }
+                }
+                div class="level-item has-text-centered" {
+                    div { p class="heading" { (text["import-failed"]) } p class="title has-text-danger" { (report.failed.len()) } }
+                }
+            }
+            @if !report.failed.is_empty() {
+                h2 class="subtitle" { (text["import-failed"]) }
+                table class="table is-fullwidth" {
+                    tbody {
+                        @for error in &report.failed {
+                            tr { td { (error.line) } td { (error.problem.text(&lang)) } }
+                        }
+                    }
+                }
+            }
+            @if !report.duplicates.is_empty() {
+                h2 class="subtitle" { (text["import-duplicates"]) }
+                table class="table is-fullwidth" {
+                    tbody {
+                        @for (line, word) in &report.duplicates {
+                            tr { td { (line) } td { (word) } }
+                        }
+                    }
+                }
+            }
+            a class="button" href=(again_url) { (text["import-again"]) }
+        },
+    )
+}
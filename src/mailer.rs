@@ -0,0 +1,279 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use lettre::{transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport as _};
+use rocket::{config::Config, fairing::AdHoc};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{db::User, types::ServerAcceptLangauge, TEXT};
+
+const ZOHO_SMTP_RELAY: &str = "smtp.zoho.com";
+const VERIFY_EMAIL_TTL_HOURS: i64 = 48;
+const RESET_PASSWORD_TTL_HOURS: i64 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Where outgoing mail ends up, chosen by `mail_transport` in Rocket.toml.
+pub trait MailTransport: Send + Sync {
+    fn send(&self, from: &str, mail: &Mail) -> Result<()>;
+}
+
+impl<T: MailTransport> MailTransport for Arc<T> {
+    fn send(&self, from: &str, mail: &Mail) -> Result<()> {
+        (**self).send(from, mail)
+    }
+}
+
+pub struct SmtpMailTransport(SmtpTransport);
+
+impl SmtpMailTransport {
+    pub fn new(username: &str, password: &str) -> Result<SmtpMailTransport> {
+        let credentials = Credentials::new(username.to_string(), password.to_string());
+        let transport = SmtpTransport::relay(ZOHO_SMTP_RELAY)?
+            .credentials(credentials)
+            .build();
+        Ok(SmtpMailTransport(transport))
+    }
+}
+
+impl MailTransport for SmtpMailTransport {
+    fn send(&self, from: &str, mail: &Mail) -> Result<()> {
+        let message = Message::builder()
+            .from(from.parse()?)
+            .to(mail.to.parse()?)
+            .subject(mail.subject.as_str())
+            .body(mail.body.clone())?;
+        self.0.send(&message)?;
+        Ok(())
+    }
+}
+
+/// Writes every mail to a file in `dir`, for development.
+pub struct FileMailTransport {
+    pub dir: PathBuf,
+}
+
+impl MailTransport for FileMailTransport {
+    fn send(&self, from: &str, mail: &Mail) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(mail_file_name(Utc::now().timestamp_millis(), &mail.to));
+        let content = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            from, mail.to, mail.subject, mail.body
+        );
+        fs::write(&path, content).with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+/// "<millis>-<recipient>.txt", with anything but letters, digits and "@._-" in the address replaced,
+/// so an address cannot point outside of the mail directory.
+pub fn mail_file_name(millis: i64, to: &str) -> String {
+    let to: String = to
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "@._-".contains(c) { c } else { '_' })
+        .collect();
+    format!("{}-{}.txt", millis, to)
+}
+
+/// Fails every mail, used when the smtp password is not configured so the site still starts.
+pub struct UnconfiguredMailTransport;
+
+impl MailTransport for UnconfiguredMailTransport {
+    fn send(&self, _from: &str, _mail: &Mail) -> Result<()> {
+        Err(anyhow!("zoho_smtp_app_password is not configured"))
+    }
+}
+
+/// Keeps sent mail in memory, for tests.
+#[derive(Default)]
+pub struct MemoryMailTransport {
+    pub sent: Mutex<Vec<Mail>>,
+}
+
+impl MailTransport for MemoryMailTransport {
+    fn send(&self, _from: &str, mail: &Mail) -> Result<()> {
+        self.sent
+            .lock()
+            .map_err(|_| anyhow!("mail outbox poisoned"))?
+            .push(mail.clone());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify",
+            TokenPurpose::ResetPassword => "reset",
+        }
+    }
+}
+
+pub struct Mailer {
+    transport: Box<dyn MailTransport>,
+    from: String,
+    site_url: String,
+    token_secret: Vec<u8>,
+}
+
+impl Mailer {
+    pub fn new(
+        transport: Box<dyn MailTransport>,
+        from: &str,
+        site_url: &str,
+        token_secret: &[u8],
+    ) -> Mailer {
+        Mailer {
+            transport,
+            from: from.to_string(),
+            site_url: site_url.trim_end_matches('/').to_string(),
+            token_secret: token_secret.to_vec(),
+        }
+    }
+
+    fn from_config(config: &Config) -> Result<Mailer> {
+        let username = config.get_str("zoho_smtp_app_username").unwrap_or("");
+        let site_url = config
+            .get_string("site_url")
+            .unwrap_or_else(|_| format!("http://{}:{}", config.address, config.port));
+        // set with ROCKET_MAIL_TOKEN_SECRET, links sent before a restart stop working without it
+        let token_secret = match config.get_str("mail_token_secret") {
+            Ok(secret) => secret.as_bytes().to_vec(),
+            Err(_) => {
+                log::warn!("mail_token_secret is not configured, using a random one");
+                rand::random::<[u8; 32]>().to_vec()
+            }
+        };
+        let transport: Box<dyn MailTransport> = match config.get_str("mail_transport").unwrap_or("smtp") {
+            "smtp" => match config.get_str("zoho_smtp_app_password") {
+                Ok(password) => Box::new(SmtpMailTransport::new(username, password)?),
+                Err(_) => {
+                    log::error!("zoho_smtp_app_password is not configured, no mail will be sent");
+                    Box::new(UnconfiguredMailTransport)
+                }
+            },
+            "file" => Box::new(FileMailTransport {
+                dir: config.get_str("mail_dir").unwrap_or("mail").into(),
+            }),
+            "memory" => Box::new(MemoryMailTransport::default()),
+            other => return Err(anyhow!("unknown mail_transport {}", other)),
+        };
+        Ok(Mailer::new(transport, username, &site_url, &token_secret))
+    }
+
+    pub fn fairing() -> AdHoc {
+        AdHoc::on_attach("Mailer", |rocket| match Mailer::from_config(rocket.config()) {
+            Ok(mailer) => Ok(rocket.manage(mailer)),
+            Err(e) => {
+                log::error!("failed to set up the mailer: {:#}", e);
+                Err(rocket)
+            }
+        })
+    }
+
+    pub fn send(&self, mail: &Mail) -> Result<()> {
+        self.transport.send(&self.from, mail)
+    }
+
+    pub fn send_verification(&self, user: &User, lang: ServerAcceptLangauge) -> Result<()> {
+        let expires_at = Utc::now() + Duration::hours(VERIFY_EMAIL_TTL_HOURS);
+        let token = self.sign_token(TokenPurpose::VerifyEmail, &user.id, expires_at, 0);
+        let link = format!("{}/verify?token={}", self.site_url, token);
+        self.send(&Mail {
+            to: user.email.clone(),
+            subject: TEXT[&lang]["mail-verify-subject"].to_string(),
+            body: format!("{}\n\n{}", TEXT[&lang]["mail-verify-body"], link),
+        })
+    }
+
+    /// `stamp` is the password's `updated_at`, so the link stops working once used.
+    pub fn send_password_reset(
+        &self,
+        user: &User,
+        stamp: i64,
+        lang: ServerAcceptLangauge,
+    ) -> Result<()> {
+        let expires_at = Utc::now() + Duration::hours(RESET_PASSWORD_TTL_HOURS);
+        let token = self.sign_token(TokenPurpose::ResetPassword, &user.id, expires_at, stamp);
+        let link = format!("{}/reset?token={}", self.site_url, token);
+        self.send(&Mail {
+            to: user.email.clone(),
+            subject: TEXT[&lang]["mail-reset-subject"].to_string(),
+            body: format!("{}\n\n{}", TEXT[&lang]["mail-reset-body"], link),
+        })
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_varkey(&self.token_secret).expect("hmac takes keys of any size")
+    }
+
+    /// `<payload>.<signature>`, both url safe base64, payload is "purpose:user_id:expires:stamp".
+    pub fn sign_token(
+        &self,
+        purpose: TokenPurpose,
+        user_id: &Uuid,
+        expires_at: DateTime<Utc>,
+        stamp: i64,
+    ) -> String {
+        let payload = format!(
+            "{}:{}:{}:{}",
+            purpose.as_str(),
+            user_id,
+            expires_at.timestamp(),
+            stamp
+        );
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = mac.finalize().into_bytes();
+        format!(
+            "{}.{}",
+            base64::encode_config(&payload, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(&signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    /// The user the token was issued to, if it is authentic, unexpired and for `purpose`.
+    pub fn verify_token(
+        &self,
+        token: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Option<(Uuid, i64)> {
+        let mut parts = token.splitn(2, '.');
+        let payload = base64::decode_config(parts.next()?, base64::URL_SAFE_NO_PAD).ok()?;
+        let signature = base64::decode_config(parts.next()?, base64::URL_SAFE_NO_PAD).ok()?;
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify(&signature).ok()?;
+
+        let payload = String::from_utf8(payload).ok()?;
+        let fields: Vec<&str> = payload.split(':').collect();
+        match fields.as_slice() {
+            [p, user_id, expires, stamp] if *p == purpose.as_str() => {
+                if expires.parse::<i64>().ok()? < now.timestamp() {
+                    return None;
+                }
+                Some((Uuid::parse_str(user_id).ok()?, stamp.parse().ok()?))
+            }
+            _ => None,
+        }
+    }
+}
@@ -11,6 +11,20 @@ extern crate lazy_static;
 
 mod types;
 mod db;
+mod scheduler;
+mod account;
+mod mailer;
+mod books;
+mod import;
+mod anki;
+mod audio;
+mod api;
+mod session;
+mod quiz;
+mod stats;
+mod words;
+mod notes;
+mod i18n;
 #[cfg(test)]
 mod tests;
 
@@ -18,121 +32,208 @@ use rocket::{
     fairing::AdHoc,
     get,
     http::{Cookie, Cookies, RawStr},
-    request::Form,
+    request::{FlashMessage, Form},
     response::{content::Plain, Flash, Redirect},
     routes, State,
 };
+use rocket_contrib::uuid::Uuid as UuidParam;
 
 use std::{
-    collections::HashMap,
     fs::File,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
+use chrono::Utc;
 use maud::{html, Markup};
 use strum::IntoEnumIterator;
-use types::{PanelRankType, ServerAcceptLangauge};
+use uuid::Uuid;
+use types::{CardState, PanelRankType, ReviewAction, ServerAcceptLangauge, StudyMode};
 use db::*;
+use i18n::TEXT;
 
 struct HitCount(AtomicUsize);
 
-lazy_static! {
-    static ref TEXT: HashMap<ServerAcceptLangauge, HashMap<&'static str, &'static str>> = [
-        (
-            ServerAcceptLangauge::SimpliedChinese,
-            [
-                ("lang-id", "zh"),
-                ("lang-name", "中文"),
-                ("site-title", "背单词，记概念，值得拥有"),
-                ("signup-login-button", "注册/登陆"),
-            ]
-            .iter()
-            .copied()
-            .collect()
-        ),
-        (
-            ServerAcceptLangauge::English,
-            [
-                ("lang-id", "en"),
-                ("lang-name", "English"),
-                (
-                    "site-title",
-                    "Memorize foreign vocab? new concept? USE the app"
-                ),
-                ("signup-login-button", "Sign Up/Login"),
-            ]
-            .iter()
-            .copied()
-            .collect()
-        ),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-}
-
 use anyhow::{anyhow, Context, Result}; //.context() //anyhow!()
 
+/// Examples shown on the back of a card, the word's page has all of them.
+const EXAMPLES_ON_CARD: usize = 3;
+
 struct AppModel {
     lang: ServerAcceptLangauge,
-    user_id: String,
-    user_action_type: String,
+    user: User,
+    user_action_type: CardState,
+    user_study_mode: StudyMode,
+    // what was typed or picked for the word, until the next word
+    user_answer: Option<String>,
+    user_verdict: Option<quiz::Verdict>,
+    // `None` outside of choice mode, or when the word has too few like it to pick from
+    user_choices: Option<quiz::Choices>,
     user_progress_idx: u32,
-    user_vocab_book_idx: u32,
+    user_vocab_book: books::Choosen,
+    // sent back by the page's forms, which must not carry the word
+    user_session_token: Uuid,
     the_word: String,
-    the_word_type: String,
-    the_word_meaning: String,
+    the_word_senses: Vec<SenseGroup>,
+    the_word_liked: bool,
+    // rendered html, shown on the back of the card
+    the_word_examples: Vec<String>,
+    the_word_note: Option<notes::Note>,
     flash_msg: Option<String>,
 }
 
-impl AppModel {
-    fn new(lang: ServerAcceptLangauge, mut cookies: Cookies, conn: &db::Connection) -> Result<AppModel> {
-        let lang = cookies
-            .get("state_choosen_lang")
-            .map_or(lang, |c| c.value().into());
-
-        let user_action_type = cookies
-            .get("user_action_type")
-            .map_or("to_answer", |c| c.value())
-            .to_string();
-
-        let flash_msg = cookies.get("_flash").map(|c| c.value().to_string());
-
-        let user_id = "xxx".to_string();
+/// The study page without a card, when the chosen words, or the whole dictionary, are empty.
+struct NothingToStudy {
+    lang: ServerAcceptLangauge,
+    user: User,
+    user_vocab_book: books::Choosen,
+    flash_msg: Option<String>,
+}
 
-        let user_progress_idx = cookies.get("vocab_idx").map_or(0, |c| c.value().parse::<i64>().unwrap()) + 1;
-        let user_vocab_book_idx = 0;
+impl AppModel {
+    fn new(
+        lang: ServerAcceptLangauge,
+        user: User,
+        flash: Option<FlashMessage>,
+        conn: &db::Connection,
+    ) -> Result<Result<AppModel, NothingToStudy>> {
+        let mut flash_msg = flash_text(flash);
 
+        let user_progress_idx = VocabProgress::count_for_user(&user.id, conn)? as u32;
+        let session = StudySession::get_or_start(&user.id, conn)?;
+        let user_vocab_book = books::choosen_book(&session, &user, conn)?;
 
-        let (the_word,_) = Vocab::get_by_idx(user_progress_idx as i64, &conn).ok().unwrap();
-        let the_word = the_word.get(0).unwrap().vocab.clone();
-        let the_word_type = "动词".to_string();
-        let the_word_meaning = "欢呼".to_string();
-        let user_progress_idx = user_progress_idx as u32;
+        // keep showing the session's card, even if another one became due meanwhile,
+        // but dictation skips words without a recording
+        let now = Utc::now();
+        let dictation = session.study_mode() == StudyMode::Dictation;
+        let keep = match &session.vocab {
+            Some(vocab) => !dictation || VocabSpeech::exists(vocab, conn)?,
+            None => false,
+        };
+        let session = if keep {
+            StudySession::touch(&user.id, now, conn)?
+        } else {
+            let scope = user_vocab_book.scope(&user.id);
+            let mut vocab = VocabProgress::next_due(&user.id, scope, dictation, now, conn)?;
+            if vocab.is_none() && dictation {
+                StudySession::set_mode(&user.id, StudyMode::Flashcard, conn)?;
+                flash_msg = Some(format!("warning {}", TEXT[&lang]["dictation-no-audio"]));
+                vocab = VocabProgress::next_due(&user.id, scope, false, now, conn)?;
+            }
+            let vocab = match vocab {
+                Some(vocab) => vocab,
+                None => return Ok(Err(NothingToStudy { lang, user, user_vocab_book, flash_msg })),
+            };
+            StudySession::update(&user.id, CardState::Answering, Some(&vocab), now, conn)?
+        };
+        let asked = quiz::Choices::of_session(&session, conn)?;
+        let user_action_type = session.card_state();
+        let user_study_mode = session.study_mode();
+        let user_answer = session.answer;
+        let user_session_token = session.token;
+        let the_word = session.vocab.context("no vocab to study")?;
+        let the_word_senses = VocabDict::group_senses(&VocabDict::get_by_word(&the_word, conn)?);
+        let the_word_liked = VocabLike::exists(&user.id, &the_word, conn)?;
+        let the_word_examples = VocabExample::get_by_word(&the_word, conn)?
+            .iter()
+            .take(EXAMPLES_ON_CARD)
+            .map(|example| makrdown_parse_clean(&example.body, Some(&the_word)))
+            .collect();
+        let the_word_note = notes::Note::load(&user.id, &the_word, conn)?;
+        let user_verdict = match (user_study_mode, &user_answer) {
+            (StudyMode::Typing, Some(answer)) | (StudyMode::Dictation, Some(answer)) => {
+                Some(quiz::check(&the_word, answer))
+            }
+            (StudyMode::Choice, Some(answer)) => match &asked {
+                Some(choices) if choices.answer() == answer => Some(quiz::Verdict::Exact),
+                _ => Some(quiz::Verdict::Wrong),
+            },
+            (StudyMode::Flashcard, _) | (_, None) => None,
+        };
+        let user_choices = match (user_study_mode, user_action_type) {
+            (StudyMode::Choice, CardState::Answering) => {
+                session::choices(&user.id, user_vocab_book.scope(&user.id), conn)?
+            }
+            _ => None,
+        };
 
-        Ok(AppModel {
+        Ok(Ok(AppModel {
             lang,
             flash_msg,
-            user_id,
+            user,
             user_action_type,
+            user_study_mode,
+            user_answer,
+            user_verdict,
+            user_choices,
             user_progress_idx,
-            user_vocab_book_idx,
+            user_vocab_book,
+            user_session_token,
             the_word,
-            the_word_type,
-            the_word_meaning,
-        })
+            the_word_senses,
+            the_word_liked,
+            the_word_examples,
+            the_word_note,
+        }))
     }
 }
 
-fn makrdown_parse_clean(input: &str) -> String {
-    use pulldown_cmark::{html, Options, Parser};
+/// The message set by a `Flash` response as "kind message", reading it clears it.
+fn flash_text(flash: Option<FlashMessage>) -> Option<String> {
+    flash.map(|flash| format!("{} {}", flash.name(), flash.msg()))
+}
+
+/// Renders Markdown to html without scripts, styles or images, with `highlight` in the text marked.
+fn makrdown_parse_clean(input: &str, highlight: Option<&str>) -> String {
+    use pulldown_cmark::{html, Event, Options, Parser};
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext(input, options);
+    let parser = Parser::new_ext(input, options).flat_map(|event| match (event, highlight) {
+        (Event::Text(text), Some(word)) => highlight_spans(&text, word)
+            .into_iter()
+            .flat_map(|(span, marked)| {
+                let span = Event::Text(span.to_string().into());
+                if marked {
+                    vec![Event::Html("<mark>".into()), span, Event::Html("</mark>".into())]
+                } else {
+                    vec![span]
+                }
+            })
+            .collect(),
+        (event, _) => vec![event],
+    });
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
-    let safe_html = ammonia::clean(&*html_output);
-    safe_html
+    // images would be fetched from anywhere by whoever views the example
+    ammonia::Builder::default()
+        .rm_tags(&["img"])
+        .clean(&html_output)
+        .to_string()
+}
+
+/// Splits `text` into spans, those `true` are `word`, in any case, at the start of a word.
+fn highlight_spans<'a>(text: &'a str, word: &str) -> Vec<(&'a str, bool)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        let at_word_start = !previous.map_or(false, char::is_alphanumeric);
+        previous = Some(c);
+        if word.is_empty() || i < start || !at_word_start {
+            continue;
+        }
+        if text.get(i..i + word.len()).map_or(false, |s| s.eq_ignore_ascii_case(word)) {
+            if start < i {
+                spans.push((&text[start..i], false));
+            }
+            spans.push((&text[i..i + word.len()], true));
+            start = i + word.len();
+        }
+    }
+    if start < text.len() {
+        spans.push((&text[start..], false));
+    }
+    spans
 }
 
 #[get("/favicon.ico")]
@@ -157,7 +258,8 @@ fn robots() -> &'static str {
 
 #[get("/api/set-lang?<lang>")]
 fn setlang(mut cookies: Cookies, lang: &RawStr) -> Result<Redirect> {
-    let cookie = Cookie::build("state_choosen_lang", lang.url_decode()?)
+    let lang = ServerAcceptLangauge::from(&*lang.url_decode()?);
+    let cookie = Cookie::build("state_choosen_lang", lang.to_string())
         .path("/")
         .secure(false)
         .finish();
@@ -167,105 +269,148 @@ fn setlang(mut cookies: Cookies, lang: &RawStr) -> Result<Redirect> {
 
 #[get("/api/order?<rankby>")]
 fn set_panel_rank(mut cookies: Cookies, rankby: &RawStr) -> Result<Redirect, std::str::Utf8Error> {
-    let cookie = Cookie::build("state_choosen_rank", rankby.url_decode()?)
+    let rank = PanelRankType::from(&*rankby.url_decode()?);
+    let rank: &'static str = rank.into();
+    let cookie = Cookie::build("state_choosen_rank", rank)
         .path("/")
         .secure(false)
         .finish();
     cookies.add(cookie);
-    Ok(Redirect::to(uri!(index)))
+    Ok(Redirect::to(uri!(words::words_page: _)))
 }
 
 #[get("/")]
 fn index(
     lang: ServerAcceptLangauge,
-    cookies: Cookies,
+    user: User,
+    flash: Option<FlashMessage>,
     hit_count: State<HitCount>,
     conn: db::Connection,
 ) -> Result<Markup> {
     hit_count.0.fetch_add(1, Ordering::Release);
-    let model = AppModel::new(lang, cookies, &conn)?;
-    Ok(default_view(&model))
+    Ok(match AppModel::new(lang, user, flash, &conn)? {
+        Ok(model) => default_view(&model),
+        Err(nothing) => nothing_to_study_view(&nothing),
+    })
 }
 
 #[derive(Debug, FromForm)]
 struct UserInput {
-    user_id: String,
-    user_action_type: String,
-    user_vocab: String,
+    user_session_token: UuidParam,
     user_progress_idx: u32,
-    user_vocab_book_idx: u32,
+}
+
+#[derive(Debug, FromForm)]
+struct AnswerInput {
+    user_session_token: UuidParam,
+    user_progress_idx: u32,
+    answer: String,
+}
+
+#[derive(Debug, FromForm)]
+struct StudyModeForm {
+    mode: StudyMode,
 }
 
 #[get("/prounciation?<vocab>")]
-fn get_prounciation(
-    lang: ServerAcceptLangauge,
-    vocab: String,
-    conn: db::Connection,
-) -> Option<Plain<Vec<u8>>> {
-    VocabSpeech::get_by_word(&vocab, &conn).ok().map(|f| Plain(f.mp3))
+fn get_prounciation(vocab: String, conn: db::Connection) -> Result<audio::AudioResponse> {
+    match VocabSpeech::get_by_word(&vocab, &conn) {
+        Ok(speech) => Ok(audio::AudioResponse::recording(speech)),
+        Err(diesel::result::Error::NotFound) => audio::AudioResponse::fallback(),
+        Err(e) => Err(e.into()),
+    }
 }
 
-#[post("/iknow", data = "<user>")]
+/// The recording of the study card, asked by the card's token so the page does not give the word away.
+#[get("/study/audio?<token>")]
+fn get_study_audio(user: User, token: UuidParam, conn: db::Connection) -> Result<Option<audio::AudioResponse>> {
+    let session = StudySession::get_or_start(&user.id, &conn)?;
+    let vocab = match session.vocab {
+        Some(vocab) if session.token == *token => vocab,
+        _ => return Ok(None),
+    };
+    match VocabSpeech::get_by_word(&vocab, &conn) {
+        Ok(speech) => Ok(Some(audio::AudioResponse::recording(speech))),
+        Err(diesel::result::Error::NotFound) => audio::AudioResponse::fallback().map(Some),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[post("/iknow", data = "<input>")]
 fn check_answer_when_know(
     lang: ServerAcceptLangauge,
-    mut cookies: Cookies,
-    user: Form<UserInput>,
-) -> Result<Redirect> {
-    let cookie = Cookie::build("user_action_type", "to_check")
-        .path("/")
-        .secure(false)
-        .finish();
-    cookies.add(cookie);
-    Ok(Redirect::to(format!("/")))
+    user: User,
+    input: Form<UserInput>,
+    conn: db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    press(lang, &user, &input, ReviewAction::Know, &conn)
 }
 
-#[post("/idontknow", data = "<user>")]
+#[post("/idontknow", data = "<input>")]
 fn check_answer_when_dontknow(
     lang: ServerAcceptLangauge,
-    mut cookies: Cookies,
-    user: Form<UserInput>,
-) -> Result<Redirect> {
-    let cookie = Cookie::build("user_action_type", "to_remember")
-        .path("/")
-        .secure(false)
-        .finish();
-    cookies.add(cookie);
-    Ok(Redirect::to(format!("/")))
+    user: User,
+    input: Form<UserInput>,
+    conn: db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    press(lang, &user, &input, ReviewAction::DontKnow, &conn)
 }
 
-#[post("/iamright", data = "<user>")]
+#[post("/iamright", data = "<input>")]
 fn get_next_question_when_right(
     lang: ServerAcceptLangauge,
-    mut cookies: Cookies,
-    user: Form<UserInput>,
-) -> Result<Redirect> {
-    let cookie = Cookie::build("user_action_type", "to_answer")
-        .path("/")
-        .secure(false)
-        .finish();
-    cookies.add(cookie);
-    Ok(Redirect::to(format!("/")))
+    user: User,
+    input: Form<UserInput>,
+    conn: db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    press(lang, &user, &input, ReviewAction::Right, &conn)
 }
 
-#[post("/iamwrong", data = "<user>")]
+#[post("/iamwrong", data = "<input>")]
 fn get_next_question_when_wrong(
     lang: ServerAcceptLangauge,
-    mut cookies: Cookies,
-    user: Form<UserInput>,
-) -> Result<Redirect> {
-    let ck_next = Cookie::build("user_action_type", "to_answer")
-        .path("/")
-        .secure(false)
-        .finish();
+    user: User,
+    input: Form<UserInput>,
+    conn: db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    press(lang, &user, &input, ReviewAction::Wrong, &conn)
+}
 
-    let ck_count = cookies.get("vocab_idx").map_or(0, |c| c.value().parse::<i64>().unwrap()) + 1;
-    let ck_count = Cookie::build("vocab_idx", ck_count.to_string())
-        .path("/")
-        .secure(false)
-        .finish();
-    cookies.add(ck_next);
-    cookies.add(ck_count);
-    Ok(Redirect::to(format!("/")))
+/// Moves the study session along, or back to the current card if the page was out of date.
+fn press(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    input: &UserInput,
+    action: ReviewAction,
+    conn: &db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    let moved = session::press(&user.id, &input.user_session_token, action, Utc::now(), conn)?;
+    Ok(back_to_study(lang, moved))
+}
+
+#[post("/ianswer", data = "<input>")]
+fn check_answer(
+    lang: ServerAcceptLangauge,
+    user: User,
+    input: Form<AnswerInput>,
+    conn: db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    let moved = session::answer(&user.id, &input.user_session_token, &input.answer, Utc::now(), &conn)?;
+    Ok(back_to_study(lang, moved))
+}
+
+fn back_to_study(lang: ServerAcceptLangauge, moved: bool) -> Result<Redirect, Flash<Redirect>> {
+    if moved {
+        Ok(Redirect::to(uri!(index)))
+    } else {
+        Err(Flash::warning(Redirect::to(uri!(index)), TEXT[&lang]["study-out-of-date"]))
+    }
+}
+
+#[post("/study-mode", data = "<form>")]
+fn set_study_mode(user: User, form: Form<StudyModeForm>, conn: db::Connection) -> Result<Redirect> {
+    StudySession::set_mode(&user.id, form.mode, &conn)?;
+    Ok(Redirect::to(uri!(index)))
 }
 
 #[get("/hitcount")]
@@ -273,10 +418,36 @@ fn hitcount(hit_count: State<HitCount>) -> String {
     hit_count.0.load(Ordering::Relaxed).to_string()
 }
 
-fn header_view(options: &AppModel) -> Markup {
-    let lang = options.lang;
+fn header_view(lang: &ServerAcceptLangauge, user: &User) -> Markup {
     html! {
        nav class="navbar" role="navigation" aria-label="main navigation" {
+           div class="navbar-start" {
+               a class="navbar-item" href=(uri!(index).to_string()) { (TEXT[lang]["nav-study"]) }
+               a class="navbar-item" href=(uri!(books::books_page).to_string()) { (TEXT[lang]["nav-books"]) }
+               a class="navbar-item" href=(uri!(words::words_page: _).to_string()) { (TEXT[lang]["nav-words"]) }
+               a class="navbar-item" href=(uri!(words::search_page: _, _).to_string()) { (TEXT[lang]["nav-search"]) }
+               a class="navbar-item" href=(uri!(stats::stats_page).to_string()) { (TEXT[lang]["nav-stats"]) }
+               @if user.is_admin {
+                   a class="navbar-item" href=(uri!(import::import_page).to_string()) { (TEXT[lang]["nav-import"]) }
+                   a class="navbar-item" href=(uri!(audio::audio_page).to_string()) { (TEXT[lang]["nav-audio"]) }
+               }
+           }
+           div class="navbar-end" {
+               @if user.email_verified_at.is_none() {
+                   div class="navbar-item" {
+                       form action=(uri!(account::resend_verification).to_string()) method="post" {
+                           span { (TEXT[lang]["email-unverified"]) " " }
+                           button class="button is-small is-warning" type="submit" { (TEXT[lang]["verify-resend"]) }
+                       }
+                   }
+               }
+               div class="navbar-item" { (user.username) }
+               div class="navbar-item" {
+                   form action=(uri!(account::logout).to_string()) method="post" {
+                       button class="button is-small is-light" type="submit" { (TEXT[lang]["logout"]) }
+                   }
+               }
+           }
        } //nav
     } //html!
 }
@@ -296,6 +467,13 @@ fn keypress_js() -> Markup {
           <script type="text/javascript">
             $(document).keypress(function(event) {
                 console.log(event.originalEvent.key);
+                // letters typed as an answer are not shortcuts
+                if ($(event.target).is('input, textarea')) {
+                    return;
+                }
+                if (/^[1-9]$/.test(event.originalEvent.key)) {
+                    $('#choice-' + event.originalEvent.key).click();
+                }
                 if (event.originalEvent.key == 'z' || event.originalEvent.key == 'Z') {
                     $('#Z').click();
                 }
@@ -347,20 +525,13 @@ fn remember_scroll_js() -> Markup {
     }
 }
 
-fn delete_cookie_js(id: &str, path: &str) -> String {
-    format!(
-        "document.cookie ='{}=; expires=Thu, 01 Jan 1970 00:00:00 UTC; path={};' ;",
-        id, path
-    )
-}
-
 fn play_audio_without_control(url :&str) -> String {
     format!("(function(){{new Audio('{}').play();}}())", url)
 }
 
-fn notification_view(options: &AppModel) -> Markup {
+fn notification_view(flash_msg: &Option<String>) -> Markup {
     let id = "notification";
-    if let Some(msg) = &options.flash_msg {
+    if let Some(msg) = flash_msg {
         //TODO map proper message to css kind
         let _message_kind: String = msg.split(" ").take(1).collect();
         let msg = msg.split(" ").skip(1).collect::<Vec<&str>>().join(" ");
@@ -369,7 +540,7 @@ fn notification_view(options: &AppModel) -> Markup {
               id=(id)
           {
               button class="delete"
-                     onclick={(toggle_js(id,"is-hidden"))(delete_cookie_js("_flash","/"))}
+                     onclick=(toggle_js(id,"is-hidden"))
               {}
               (msg)
           }
@@ -379,8 +550,7 @@ fn notification_view(options: &AppModel) -> Markup {
     }
 }
 
-fn default_view(model: &AppModel) -> Markup {
-    let lang = &model.lang;
+fn page_view(lang: &ServerAcceptLangauge, content: Markup) -> Markup {
     html! {
       head {
           meta charset="utf-8" {}
@@ -392,22 +562,103 @@ fn default_view(model: &AppModel) -> Markup {
           title { (TEXT[lang]["site-title"]) }
       }
       body {
-        (main_view(model))
-        (keypress_js())
-        (timeago_js())
-        (remember_scroll_js())
+        (content)
         (development_script_tag())
       }
     }
 }
 
+/// A plain page of the logged-in app, for everything besides the flashcard.
+fn app_page_view(
+    lang: &ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    content: Markup,
+) -> Markup {
+    page_view(
+        lang,
+        html! {
+            (header_view(lang, user))
+            section class="section" {
+                div class="container" {
+                    (notification_view(flash_msg))
+                    (content)
+                }
+            }
+        },
+    )
+}
+
+fn default_view(model: &AppModel) -> Markup {
+    page_view(
+        &model.lang,
+        html! {
+            (main_view(model))
+            (keypress_js())
+            (timeago_js())
+            (remember_scroll_js())
+        },
+    )
+}
+
+fn nothing_to_study_view(model: &NothingToStudy) -> Markup {
+    let text = &TEXT[&model.lang];
+    app_page_view(
+        &model.lang,
+        &model.user,
+        &model.flash_msg,
+        html! {
+            div class="box has-text-centered" {
+                @match &model.user_vocab_book {
+                    books::Choosen::AllWords => {
+                        p class="block" { (text["dictionary-empty"]) }
+                        @if model.user.is_admin {
+                            a class="button is-primary" href=(uri!(import::import_page).to_string()) { (text["nav-import"]) }
+                        }
+                    }
+                    choosen => {
+                        // every favorite may have been unliked since they were chosen
+                        @if let books::Choosen::Favorites = choosen {
+                            p class="block" { (text["favorites-empty"]) }
+                        } @else {
+                            p class="block" { (text["book-nothing-to-study"]) }
+                        }
+                        div class="buttons is-centered" {
+                            form action=(uri!(books::study_all).to_string()) method="post" {
+                                button class="button is-primary" type="submit" { (text["study-all-words"]) }
+                            }
+                            @if let books::Choosen::Favorites = choosen {
+                                a class="button" href=(uri!(words::words_page: _).to_string()) { (text["nav-words"]) }
+                            } @else {
+                                a class="button" href=(uri!(books::books_page).to_string()) { (text["nav-books"]) }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
 fn main_view(model: &AppModel) -> Markup {
+    let text = &TEXT[&model.lang];
+    // typing, and picking the word for a meaning, hide the word and its pronunciation until it is answered,
+    // dictation hides everything but the pronunciation
+    let picking = model.user_choices.as_ref();
+    let (show_word, revealed, audible) = match (model.user_study_mode, model.user_action_type) {
+        (StudyMode::Flashcard, CardState::Answering) => (true, false, true),
+        (StudyMode::Typing, CardState::Answering) => (false, true, false),
+        (StudyMode::Choice, CardState::Answering) => {
+            let by_word = picking.map_or(true, |c| c.by_word);
+            (by_word, false, by_word)
+        }
+        (StudyMode::Dictation, CardState::Answering) => (false, false, true),
+        (_, CardState::Checking) | (_, CardState::Remembering) | (_, CardState::Reviewing) => (true, true, true),
+    };
+    let play_word = play_audio_without_control(&format!("/study/audio?token={}", model.user_session_token));
     let hidden_inputs = html! {
-        input type="hidden" name="user_id" value=(model.user_id) {}
-        input type="hidden" name="user_action_type" value=(model.user_action_type) {}
-        input type="hidden" name="user_vocab" value=(model.the_word) {}
+        input type="hidden" name="user_session_token" value=(model.user_session_token) {}
         input type="hidden" name="user_progress_idx" value=(model.user_progress_idx) {}
-        input type="hidden" name="user_vocab_book_idx" value=(model.user_vocab_book_idx) {}
     };
 
     let circle_icon_with_overlay_z = html! {
@@ -442,18 +693,90 @@ fn main_view(model: &AppModel) -> Markup {
     };
     html! {
         section class="hero is-primary is-fullheight " {
+            div class="hero-head" {
+                (header_view(&model.lang, &model.user))
+            }
             div class="hero-body" {
                 div class="container" {
                     div class="columns is-centered" {
                         div class="column is-half-tablet is-one-third-desktop is-one-quarter-widescreen" {
+                            (notification_view(&model.flash_msg))
+                            (study_mode_view(model))
                             div class="box has-text-centered" {
-                                p class="title is-1 has-text-black" {(model.the_word)}
-                                @if model.user_action_type == "to_answer" {
-                                    p class="subtitle is-6" {(model.the_word_type)}
-                                    p class="subtitle is-2" {(model.the_word_meaning)}
-                                } @else {
-                                    p class="subtitle is-6 has-text-grey" {(model.the_word_type)}
-                                    p class="subtitle is-2 has-text-black" {(model.the_word_meaning)}
+                                div class="level is-mobile" {
+                                    div class="level-left" {
+                                        @match &model.user_vocab_book {
+                                            books::Choosen::Book(book) => { span class="level-item tag is-light" { (book.name) } }
+                                            books::Choosen::Favorites => { span class="level-item tag is-light" { (text["book-favorites"]) } }
+                                            books::Choosen::AllWords => {}
+                                        }
+                                    }
+                                    // the like button posts the word
+                                    @if show_word {
+                                        div class="level-right" {
+                                            span class="level-item" { (words::like_button(&model.lang, &model.the_word, model.the_word_liked, "/")) }
+                                        }
+                                    }
+                                }
+                                @if show_word {
+                                    p class="title is-1 has-text-black" {(model.the_word)}
+                                }
+                                @if let Some(choices) = picking {
+                                    (choices_view(choices, &hidden_inputs))
+                                }
+                                @if let (StudyMode::Typing, CardState::Answering) | (StudyMode::Dictation, CardState::Answering) = (model.user_study_mode, model.user_action_type) {
+                                    // browsers that block autoplay leave the play button
+                                    @if model.user_study_mode == StudyMode::Dictation {
+                                        script { (maud::PreEscaped(&play_word)) }
+                                    }
+                                    form action="/ianswer" method="post" id="itype" {
+                                        (hidden_inputs)
+                                        div class="field has-addons" {
+                                            div class="control is-expanded" {
+                                                input class="input is-large" type="text" name="answer"
+                                                      placeholder=(text[if model.user_study_mode == StudyMode::Dictation { "dictation-placeholder" } else { "typing-placeholder" }])
+                                                      autocomplete="off" autocapitalize="off" spellcheck="false" autofocus? {}
+                                            }
+                                            div class="control" {
+                                                button class="button is-black is-large" type="submit" { (text["typing-submit"]) }
+                                            }
+                                        }
+                                    }
+                                }
+                                @if let (CardState::Reviewing, Some(answer), Some(verdict)) = (model.user_action_type, &model.user_answer, model.user_verdict) {
+                                    @match (model.user_study_mode, verdict) {
+                                        (_, quiz::Verdict::Exact) => { p class="has-text-success" { (text["typing-exact"]) } }
+                                        // picked answers are either right or wrong
+                                        (StudyMode::Choice, _) => {
+                                            p class="has-text-danger" { (text["choice-wrong"]) }
+                                            p class="has-text-black" { (text["choice-picked"]) " " del { (answer) } }
+                                        }
+                                        (_, quiz::Verdict::Close) => {
+                                            p class="has-text-success" { (text["typing-close"]) }
+                                            (quiz::diff_view(&model.the_word, answer))
+                                        }
+                                        (_, quiz::Verdict::Wrong) => {
+                                            p class="has-text-danger" { (text["typing-wrong"]) }
+                                            (quiz::diff_view(&model.the_word, answer))
+                                        }
+                                    }
+                                }
+                                (senses_view(&model.the_word_senses, revealed))
+                                // notes and examples would give away a word still to be typed
+                                @if model.user_action_type == CardState::Remembering {
+                                    @if let Some(note) = &model.the_word_note {
+                                        (notes::note_view(&model.lang, note, true))
+                                    }
+                                    (notes::note_form(&model.lang, &model.the_word, model.the_word_note.as_ref(), "/"))
+                                } @else if revealed && show_word {
+                                    @if let Some(note) = &model.the_word_note {
+                                        (notes::note_view(&model.lang, note, true))
+                                    }
+                                }
+                                @if revealed && show_word {
+                                    @for example in &model.the_word_examples {
+                                        div class="content has-text-left has-text-black" { (maud::PreEscaped(example)) }
+                                    }
                                 }
                             }
                             form action="/iknow" method="post" id="iknow" {
@@ -469,54 +792,76 @@ fn main_view(model: &AppModel) -> Markup {
                                 (hidden_inputs)
                             }
                             div class="level is-mobile" {
-                                div class="level-item" {
-                                    button class="button is-black" type="submit" id="Z"
-                                           onclick=(play_audio_without_control(uri!(get_prounciation: &model.the_word).to_string().as_ref()))
-                                    {
-                                        (circle_icon_with_overlay_z)
-                                        span { "发音" }
+                                @if audible {
+                                    div class="level-item" {
+                                        button class="button is-black" type="submit" id="Z" onclick=(play_word) {
+                                            (circle_icon_with_overlay_z)
+                                            span { (text["button-play"]) }
+                                        }
                                     }
                                 }
-                                @if model.user_action_type == "to_answer" {
+                                @match (model.user_study_mode, model.user_action_type, picking.is_some()) {
+                                    (StudyMode::Flashcard, CardState::Answering, _) | (StudyMode::Choice, CardState::Answering, false) => {
                                         div class="level-item" {
                                             button class="button is-black" type="submit" form="iknow" id="X" {
                                                 (circle_icon_with_overlay_x)
-                                                span { "知道" }
+                                                span { (text["button-know"]) }
                                             }
                                         }
                                         div class="level-item" {
                                             button class="button is-black" type="submit" form="idontknow" id="C" {
                                                 (circle_icon_with_overlay_c)
-                                                span { "不知道" }
+                                                span { (text["button-dontknow"]) }
                                             }
                                         }
-                                } @else if model.user_action_type == "to_check" {
+                                    },
+                                    (StudyMode::Typing, CardState::Answering, _)
+                                    | (StudyMode::Dictation, CardState::Answering, _)
+                                    | (StudyMode::Choice, CardState::Answering, true) => {
+                                        div class="level-item" {
+                                            button class="button is-black" type="submit" form="idontknow" id="C" {
+                                                (circle_icon_with_overlay_c)
+                                                span { (text["button-dontknow"]) }
+                                            }
+                                        }
+                                    },
+                                    (_, CardState::Checking, _) => {
                                         div class="level-item" {
                                             button class="button is-black" type="submit" form="iamright" id="X" {
                                                 (circle_icon_with_overlay_x)
-                                                span { "正确" }
+                                                span { (text["button-right"]) }
                                             }
                                         }
                                         div class="level-item" {
                                             button class="button is-black" type="submit" form="iamwrong" id="C" {
                                                 (circle_icon_with_overlay_c)
-                                                span { "记错了" }
+                                                span { (text["button-wrong"]) }
                                             }
                                         }
-                                } @ else {
+                                    },
+                                    (_, CardState::Remembering, _) => {
                                         div class="level-item" {
                                             button class="button is-black" type="submit" form="iamright" id="X" {
                                                 (circle_icon_with_overlay_x)
-                                                span { "跳过" }
+                                                span { (text["button-skip"]) }
                                             }
                                         }
                                         div class="level-item" {
                                             button class="button is-black" type="submit" form="iamwrong" id="C" {
                                                 (circle_icon_with_overlay_c)
-                                                span { "下一个" }
+                                                span { (text["button-next"]) }
                                             }
                                         }
-                               }
+                                    },
+                                    (_, CardState::Reviewing, _) => {
+                                        div class="level-item" {
+                                            button class="button is-black" type="submit" form="iamright" id="X" autofocus? {
+                                                (circle_icon_with_overlay_x)
+                                                span { (text["button-next"]) }
+                                            }
+                                        }
+                                    },
+                                }
                             }
                         }
                     }
@@ -526,6 +871,70 @@ fn main_view(model: &AppModel) -> Markup {
     }
 }
 
+/// Switches how the study page asks for words.
+fn study_mode_view(model: &AppModel) -> Markup {
+    let text = &TEXT[&model.lang];
+    html! {
+        div class="buttons has-addons is-centered" {
+            @for mode in StudyMode::iter() {
+                form action="/study-mode" method="post" {
+                    input type="hidden" name="mode" value=(mode.as_str()) {}
+                    @if mode == model.user_study_mode {
+                        button class="button is-small is-selected is-dark" type="submit" { (text[mode_text_key(mode)]) }
+                    } @else {
+                        button class="button is-small" type="submit" { (text[mode_text_key(mode)]) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn mode_text_key(mode: StudyMode) -> &'static str {
+    match mode {
+        StudyMode::Flashcard => "mode-flashcard",
+        StudyMode::Typing => "mode-typing",
+        StudyMode::Choice => "mode-choice",
+        StudyMode::Dictation => "mode-dictation",
+    }
+}
+
+/// One button per option, numbered for the number key shortcuts.
+fn choices_view(choices: &quiz::Choices, hidden_inputs: &Markup) -> Markup {
+    html! {
+        @if !choices.by_word {
+            p class="subtitle is-6 has-text-grey" { (choices.sense.partofspeech) }
+            p class="title is-4 has-text-black" { (choices.sense.meaning) }
+        }
+        @for (i, option) in choices.options.iter().enumerate() {
+            form action="/ianswer" method="post" class="block" {
+                (hidden_inputs)
+                input type="hidden" name="answer" value=(option) {}
+                button class="button is-fullwidth is-light" type="submit" id={"choice-" (i + 1)} {
+                    span class="has-text-grey" { (i + 1) ". " }
+                    span { (option) }
+                }
+            }
+        }
+    }
+}
+
+/// Meanings grouped by part of speech, only the parts of speech until `revealed`.
+fn senses_view(senses: &[SenseGroup], revealed: bool) -> Markup {
+    html! {
+        @for group in senses {
+            div class="block" {
+                p class="subtitle is-6 has-text-grey" {(group.partofspeech)}
+                @if revealed {
+                    @for meaning in &group.meanings {
+                        p class="subtitle is-4 has-text-black" {(meaning)}
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[cfg(debug_assertions)]
 fn development_script_tag() -> Markup {
     html! {
@@ -550,16 +959,88 @@ fn rocket() -> rocket::Rocket {
                 instantclick,
                 hitcount,
                 get_prounciation,
+                get_study_audio,
                 get_next_question_when_right,
                 get_next_question_when_wrong,
                 check_answer_when_know,
                 check_answer_when_dontknow,
+                check_answer,
+                set_study_mode,
+                account::signup_page,
+                account::signup,
+                account::login_page,
+                account::login,
+                account::logout,
+                account::verify_email,
+                account::resend_verification,
+                account::forgot_page,
+                account::forgot,
+                account::reset_page,
+                account::reset,
+                books::books_page,
+                books::create_book,
+                books::book_page,
+                books::rename_book,
+                books::delete_book,
+                books::add_book_word,
+                books::remove_book_word,
+                books::study_book,
+                books::study_all,
+                books::favorites_page,
+                books::study_favorites,
+                import::import_page,
+                import::import_upload,
+                anki::import_page,
+                anki::import_upload,
+                anki::export_book,
+                audio::audio_page,
+                audio::audio_upload,
+                stats::stats_page,
+                set_panel_rank,
+                words::words_page,
+                words::like_word,
+                words::search_page,
+                words::word_page,
+                words::add_to_book,
+                words::add_example,
+                words::delete_example,
+                notes::save_note,
+                words::unlike_word,
             ],
         )
+        .mount(
+            api::BASE,
+            routes![
+                api::create_token,
+                api::delete_token,
+                api::next_card,
+                api::grade_card,
+                api::books,
+                api::search,
+                api::stats,
+            ],
+        )
+        .register(catchers![account::unauthorized])
         .manage(HitCount(AtomicUsize::new(0)))
         .attach(db::Connection::fairing())
+        .attach(mailer::Mailer::fairing())
 }
 
 fn main() {
-    rocket().launch();
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some(command @ "import") | Some(command @ "audio") => {
+            let result = match command {
+                "import" => import::run_cli(&args[2..]),
+                _ => audio::run_cli(&args[2..]),
+            };
+            if let Err(e) = result {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            rocket().launch();
+        }
+    }
 }
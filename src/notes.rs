@@ -0,0 +1,111 @@
+// The learner's own notes and memory hooks for a word, written when a word was not known
+// and shown again whenever it comes back.
+use rocket::{
+    request::Form,
+    response::{Flash, Redirect},
+};
+use rocket_contrib::databases::diesel::{PgConnection, QueryResult};
+
+use maud::{html, Markup, PreEscaped};
+use uuid::Uuid;
+
+use crate::{db, db::*, makrdown_parse_clean, words::back_url, TEXT};
+use crate::types::ServerAcceptLangauge;
+
+use anyhow::Result;
+
+pub const MAX_NOTE_LEN: usize = 2000;
+
+/// A note and its rendered html.
+pub struct Note {
+    pub body: String,
+    pub html: String,
+}
+
+impl Note {
+    pub fn load(user_id: &Uuid, vocab: &str, conn: &PgConnection) -> Result<Option<Note>> {
+        Ok(VocabNote::get(user_id, vocab, conn)?.map(|note| Note {
+            html: makrdown_parse_clean(&note.body, Some(vocab)),
+            body: note.body,
+        }))
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct NoteForm {
+    vocab: String,
+    body: String,
+    /// the page the editor is on
+    back: String,
+}
+
+/// Saves the note of the word, an empty one deletes it.
+#[post("/notes", data = "<form>")]
+pub fn save_note(
+    lang: ServerAcceptLangauge,
+    user: User,
+    form: Form<NoteForm>,
+    conn: db::Connection,
+) -> Result<Flash<Redirect>> {
+    let back = Redirect::to(back_url(&form.back));
+    Ok(match save(&user.id, &form.vocab, &form.body, &conn)? {
+        Saved::Written => Flash::success(back, TEXT[&lang]["note-saved"]),
+        Saved::Deleted => Flash::success(back, TEXT[&lang]["note-deleted"]),
+        Saved::TooLong => Flash::warning(back, TEXT[&lang]["note-too-long"]),
+        Saved::UnknownWord => Flash::warning(back, TEXT[&lang]["word-unknown"]),
+    })
+}
+
+/// What saving a note did.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Saved {
+    Written,
+    /// the body was empty
+    Deleted,
+    TooLong,
+    UnknownWord,
+}
+
+/// Writes the user's note of `vocab`, replacing the one there was, or deletes it when `body` is blank.
+pub fn save(user_id: &Uuid, vocab: &str, body: &str, conn: &PgConnection) -> QueryResult<Saved> {
+    let body = body.trim();
+    if body.is_empty() {
+        VocabNote::delete(user_id, vocab, conn)?;
+        return Ok(Saved::Deleted);
+    }
+    if body.chars().count() > MAX_NOTE_LEN {
+        return Ok(Saved::TooLong);
+    }
+    if !Vocab::exists(vocab, conn)? {
+        return Ok(Saved::UnknownWord);
+    }
+    VocabNote::save(&NewVocabNote { user_id, vocab, body }, conn)?;
+    Ok(Saved::Written)
+}
+
+/// The note, folded unless `open`.
+pub fn note_view(lang: &ServerAcceptLangauge, note: &Note, open: bool) -> Markup {
+    html! {
+        details class="block has-text-left" open?[open] {
+            summary class="has-text-grey" { (TEXT[lang]["note-title"]) }
+            div class="content has-text-black" { (PreEscaped(&note.html)) }
+        }
+    }
+}
+
+/// An editor of the note of `vocab` that comes back to `back`.
+pub fn note_form(lang: &ServerAcceptLangauge, vocab: &str, note: Option<&Note>, back: &str) -> Markup {
+    html! {
+        form class="block has-text-left" action=(uri!(save_note).to_string()) method="post" {
+            input type="hidden" name="vocab" value=(vocab) {}
+            input type="hidden" name="back" value=(back) {}
+            div class="field" {
+                textarea class="textarea" name="body" rows="3" maxlength=(MAX_NOTE_LEN)
+                         placeholder=(TEXT[lang]["note-placeholder"]) {
+                    @if let Some(note) = note { (note.body) }
+                }
+            }
+            button class="button is-small" type="submit" { (TEXT[lang]["note-save"]) }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// Grading a typed answer against the word: forgiving about case, accents and spacing, and a typo
+// or two in longer words. Multiple choice questions are put together here too.
+use rocket_contrib::databases::diesel::{PgConnection, QueryResult};
+
+use maud::{html, Markup};
+use rand::seq::SliceRandom;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{db::{StudySession, VocabDict, WordScope}, scheduler::Grade};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Exact,
+    /// within the typos allowed for the length of the word
+    Close,
+    Wrong,
+}
+
+impl Verdict {
+    pub fn grade(self) -> Grade {
+        match self {
+            Verdict::Exact | Verdict::Close => Grade::Recalled,
+            Verdict::Wrong => Grade::Mistaken,
+        }
+    }
+}
+
+/// One character of the word against what was typed there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Same(char),
+    /// in the word but not typed
+    Missing(char),
+    /// typed but not in the word
+    Extra(char),
+    Replaced { expected: char, typed: char },
+}
+
+/// Lowercase, without accents, with single spaces between words: "Café  au lait " is "cafe au lait".
+pub fn normalize(text: &str) -> String {
+    let folded: String = text
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Typos allowed for a word of `len` characters.
+fn tolerance(len: usize) -> usize {
+    match len {
+        0..=4 => 0,
+        5..=8 => 1,
+        _ => 2,
+    }
+}
+
+pub fn check(word: &str, typed: &str) -> Verdict {
+    let word = normalize(word);
+    let typed = normalize(typed);
+    if word == typed {
+        return Verdict::Exact;
+    }
+    if typed.is_empty() {
+        return Verdict::Wrong;
+    }
+    let word: Vec<char> = word.chars().collect();
+    let typed: Vec<char> = typed.chars().collect();
+    if distances(&word, &typed)[word.len()][typed.len()] <= tolerance(word.len()) {
+        Verdict::Close
+    } else {
+        Verdict::Wrong
+    }
+}
+
+/// `table[i][j]` is the distance between the first `i` characters of `a` and the first `j` of `b`.
+fn distances(a: &[char], b: &[char]) -> Vec<Vec<usize>> {
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..=a.len() {
+        table[i][0] = i;
+    }
+    for j in 0..=b.len() {
+        table[0][j] = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replace = table[i - 1][j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            table[i][j] = replace.min(table[i - 1][j] + 1).min(table[i][j - 1] + 1);
+        }
+    }
+    table
+}
+
+/// The fewest edits turning the typed answer into the word, both normalized.
+pub fn diff(word: &str, typed: &str) -> Vec<Edit> {
+    let a: Vec<char> = normalize(word).chars().collect();
+    let b: Vec<char> = normalize(typed).chars().collect();
+    let table = distances(&a, &b);
+    let (mut i, mut j) = (a.len(), b.len());
+    let mut edits = Vec::new();
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && a[i - 1] == b[j - 1] && table[i][j] == table[i - 1][j - 1] {
+            edits.push(Edit::Same(a[i - 1]));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && j > 0 && table[i][j] == table[i - 1][j - 1] + 1 {
+            edits.push(Edit::Replaced {
+                expected: a[i - 1],
+                typed: b[j - 1],
+            });
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && table[i][j] == table[i - 1][j] + 1 {
+            edits.push(Edit::Missing(a[i - 1]));
+            i -= 1;
+        } else {
+            edits.push(Edit::Extra(b[j - 1]));
+            j -= 1;
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/// The typed answer with wrong letters struck out and the right ones inserted.
+pub fn diff_view(word: &str, typed: &str) -> Markup {
+    html! {
+        p class="is-size-4 has-text-black" style="font-family: monospace" {
+            @for edit in diff(word, typed) {
+                @match edit {
+                    Edit::Same(c) => { span { (c) } }
+                    Edit::Missing(c) => { ins class="has-text-success" { (c) } }
+                    Edit::Extra(c) => { del class="has-text-danger" { (c) } }
+                    Edit::Replaced { expected, typed } => {
+                        del class="has-text-danger" { (typed) }
+                        ins class="has-text-success" { (expected) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A sense of the word asked with options to pick from, one of them right.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choices {
+    /// the options are meanings for the word, otherwise words for the meaning of `sense`
+    pub by_word: bool,
+    pub sense: VocabDict,
+    pub options: Vec<String>,
+}
+
+impl Choices {
+    /// The question kept in the session for its card, `None` if none was asked yet.
+    pub fn of_session(session: &StudySession, connection: &PgConnection) -> QueryResult<Option<Choices>> {
+        let (sense_id, options) = match (&session.choice_sense, &session.choice_options) {
+            (Some(sense_id), Some(options)) => (sense_id, options),
+            _ => return Ok(None),
+        };
+        Ok(VocabDict::get(sense_id, connection)?.map(|sense| Choices {
+            by_word: session.choice_by_word,
+            sense,
+            options: options.clone(),
+        }))
+    }
+
+    /// The meaning of the sense asked, or the word for it.
+    pub fn answer(&self) -> &str {
+        if self.by_word {
+            &self.sense.meaning
+        } else {
+            &self.sense.vocab
+        }
+    }
+
+    pub fn is_offered(&self, choice: &str) -> bool {
+        self.options.iter().any(|option| option == choice)
+    }
+}
+
+pub const CHOICE_COUNT: usize = 4;
+
+/// Asks one random sense of `word`, with distractors of the same part of speech from `scope` first
+/// and then from the whole dictionary. `None` when there are not enough of them.
+pub fn choices(
+    word: &str,
+    scope: WordScope,
+    by_word: bool,
+    connection: &PgConnection,
+) -> QueryResult<Option<Choices>> {
+    let mut rng = rand::thread_rng();
+    let senses = VocabDict::get_by_word(word, connection)?;
+    let sense = match senses.choose(&mut rng) {
+        Some(sense) => sense.clone(),
+        None => return Ok(None),
+    };
+    let mut options = vec![if by_word { sense.meaning.clone() } else { word.to_string() }];
+    // a few more than needed, some may turn out to be synonyms
+    let limit = (CHOICE_COUNT * 3) as i64;
+    for pool in [scope, WordScope::AllWords].iter() {
+        if options.len() < CHOICE_COUNT {
+            let like = VocabDict::like(word, &sense.partofspeech, *pool, limit, connection)?;
+            add_distractors(&mut options, by_word, &senses, like);
+        }
+    }
+    if options.len() < CHOICE_COUNT {
+        return Ok(None);
+    }
+    options.shuffle(&mut rng);
+    Ok(Some(Choices { by_word, sense, options }))
+}
+
+/// Fills `options` up to `CHOICE_COUNT` with senses of other words that cannot also be right.
+pub fn add_distractors(options: &mut Vec<String>, by_word: bool, senses: &[VocabDict], like: Vec<VocabDict>) {
+    for other in like {
+        if options.len() >= CHOICE_COUNT {
+            break;
+        }
+        if senses.iter().any(|s| s.meaning == other.meaning) {
+            continue;
+        }
+        let option = if by_word { other.meaning } else { other.vocab };
+        if !options.contains(&option) {
+            options.push(option);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// SM-2 spaced repetition, see https://www.supermemo.com/en/archives1990-2015/english/ol/sm2
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+pub const DEFAULT_EASE_FACTOR: f64 = 2.5;
+const MIN_EASE_FACTOR: f64 = 1.3;
+// a failed card comes back within the same study session
+const RELEARN_DELAY_MINUTES: i64 = 10;
+
+/// What the learner told us about a card once it is finished.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grade {
+    /// "不知道": did not remember the word at all
+    Forgot,
+    /// "知道" then "记错了": thought it was known but got it wrong
+    Mistaken,
+    /// "知道" then "正确"
+    Recalled,
+}
+
+impl Grade {
+    /// SM-2 response quality, 0 (blackout) to 5 (perfect)
+    pub fn quality(self) -> i32 {
+        match self {
+            Grade::Forgot => 1,
+            Grade::Mistaken => 2,
+            Grade::Recalled => 4,
+        }
+    }
+
+    pub fn is_pass(self) -> bool {
+        self.quality() >= 3
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Schedule {
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub lapses: i32,
+    pub due_at: DateTime<Utc>,
+}
+
+impl Schedule {
+    /// A card that has never been reviewed, due right away.
+    pub fn new(now: DateTime<Utc>) -> Schedule {
+        Schedule {
+            ease_factor: DEFAULT_EASE_FACTOR,
+            interval_days: 0,
+            repetitions: 0,
+            lapses: 0,
+            due_at: now,
+        }
+    }
+
+    pub fn review(&self, grade: Grade, now: DateTime<Utc>) -> Schedule {
+        let q = grade.quality() as f64;
+        let ease_factor = (self.ease_factor + (0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)))
+            .max(MIN_EASE_FACTOR);
+
+        if grade.is_pass() {
+            let interval_days = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval_days as f64 * self.ease_factor).round() as i32,
+            };
+            Schedule {
+                ease_factor,
+                interval_days,
+                repetitions: self.repetitions + 1,
+                lapses: self.lapses,
+                due_at: now + Duration::days(interval_days as i64),
+            }
+        } else {
+            Schedule {
+                ease_factor,
+                interval_days: 0,
+                repetitions: 0,
+                // forgetting a word never learned is not a lapse
+                lapses: self.lapses + (self.repetitions > 0) as i32,
+                due_at: now + Duration::minutes(RELEARN_DELAY_MINUTES),
+            }
+        }
+    }
+}
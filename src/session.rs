@@ -0,0 +1,145 @@
+// The study flow: a word is answered, then checked, remembered or reviewed, then the next word comes.
+use rocket_contrib::databases::diesel::{Connection, PgConnection, QueryResult};
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    db::{ReviewLog, StudySession, Vocab, VocabProgress, WordScope},
+    quiz,
+    scheduler::Grade,
+    types::{CardState, ReviewAction, StudyMode},
+};
+
+/// What a button press leads to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// show the meanings of the same word
+    Reveal(CardState),
+    /// schedule the word and go on to the next one
+    Finish(Grade),
+    /// go on to the next word, this one was scheduled when its answer was typed
+    Next,
+}
+
+/// What pressing `action` on a card in `state` does, `None` when the page has no such button.
+pub fn next(mode: StudyMode, state: CardState, action: ReviewAction) -> Option<Step> {
+    match state {
+        CardState::Answering => match (mode, action) {
+            // choice mode asks this way when there are too few words to pick from
+            (StudyMode::Flashcard, ReviewAction::Know) | (StudyMode::Choice, ReviewAction::Know) => {
+                Some(Step::Reveal(CardState::Checking))
+            }
+            // typed answers are checked by `answer`
+            (StudyMode::Typing, ReviewAction::Know) | (StudyMode::Dictation, ReviewAction::Know) => None,
+            (_, ReviewAction::DontKnow) => Some(Step::Reveal(CardState::Remembering)),
+            (_, ReviewAction::Right) | (_, ReviewAction::Wrong) => None,
+        },
+        CardState::Checking => match action {
+            ReviewAction::Right => Some(Step::Finish(Grade::Recalled)),
+            ReviewAction::Wrong => Some(Step::Finish(Grade::Mistaken)),
+            ReviewAction::Know | ReviewAction::DontKnow => None,
+        },
+        CardState::Remembering => match action {
+            ReviewAction::Right | ReviewAction::Wrong => Some(Step::Finish(Grade::Forgot)),
+            ReviewAction::Know | ReviewAction::DontKnow => None,
+        },
+        CardState::Reviewing => match action {
+            ReviewAction::Right | ReviewAction::Wrong => Some(Step::Next),
+            ReviewAction::Know | ReviewAction::DontKnow => None,
+        },
+    }
+}
+
+/// Applies a button pressed on the page of the session's `token`, returns `false` when the session
+/// has moved on since, e.g. the page was answered in another tab already.
+pub fn press(
+    user_id: &Uuid,
+    token: &Uuid,
+    action: ReviewAction,
+    now: DateTime<Utc>,
+    connection: &PgConnection,
+) -> QueryResult<bool> {
+    connection.transaction(|| {
+        let session = StudySession::lock(user_id, connection)?;
+        let vocab = match session.vocab.as_deref() {
+            Some(vocab) if session.token == *token => vocab,
+            _ => return Ok(false),
+        };
+        let step = match next(session.study_mode(), session.card_state(), action) {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        match step {
+            Step::Reveal(revealed) => {
+                ReviewLog::insert(user_id, vocab, action, None, session.latency_ms(now), connection)?;
+                StudySession::update(user_id, revealed, Some(vocab), now, connection)?
+            }
+            Step::Finish(grade) => {
+                ReviewLog::insert(user_id, vocab, action, Some(grade), session.latency_ms(now), connection)?;
+                Vocab::count_review(vocab, connection)?;
+                VocabProgress::review(user_id, vocab, grade, now, connection)?;
+                StudySession::update(user_id, CardState::Answering, None, now, connection)?
+            }
+            Step::Next => StudySession::update(user_id, CardState::Answering, None, now, connection)?,
+        };
+        Ok(true)
+    })
+}
+
+/// Grades `answer` typed or picked on the page of the session's `token` like "正确" or "记错了" would,
+/// returns `false` when the session has moved on since.
+pub fn answer(
+    user_id: &Uuid,
+    token: &Uuid,
+    answer: &str,
+    now: DateTime<Utc>,
+    connection: &PgConnection,
+) -> QueryResult<bool> {
+    connection.transaction(|| {
+        let session = StudySession::lock(user_id, connection)?;
+        let vocab = match session.vocab.as_deref() {
+            Some(vocab) if session.token == *token && session.card_state() == CardState::Answering => vocab,
+            _ => return Ok(false),
+        };
+        let grade = match session.study_mode() {
+            StudyMode::Flashcard => return Ok(false),
+            StudyMode::Typing | StudyMode::Dictation => quiz::check(vocab, answer).grade(),
+            // only the options on the page count
+            StudyMode::Choice => match quiz::Choices::of_session(&session, connection)? {
+                Some(choices) if choices.answer() == answer => Grade::Recalled,
+                Some(choices) if choices.is_offered(answer) => Grade::Mistaken,
+                _ => return Ok(false),
+            },
+        };
+        let action = match grade {
+            Grade::Recalled => ReviewAction::Right,
+            Grade::Mistaken | Grade::Forgot => ReviewAction::Wrong,
+        };
+        ReviewLog::insert(user_id, vocab, action, Some(grade), session.latency_ms(now), connection)?;
+        Vocab::count_review(vocab, connection)?;
+        VocabProgress::review(user_id, vocab, grade, now, connection)?;
+        StudySession::review_answer(user_id, answer, now, connection)?;
+        Ok(true)
+    })
+}
+
+/// The multiple choice question for the session's card, put together the first time it is shown and
+/// kept so reloading the page asks the same. `None` when the word has too few like it to pick from.
+pub fn choices(user_id: &Uuid, scope: WordScope, connection: &PgConnection) -> QueryResult<Option<quiz::Choices>> {
+    connection.transaction(|| {
+        let session = StudySession::lock(user_id, connection)?;
+        if let Some(choices) = quiz::Choices::of_session(&session, connection)? {
+            return Ok(Some(choices));
+        }
+        let vocab = match &session.vocab {
+            Some(vocab) => vocab,
+            None => return Ok(None),
+        };
+        let choices = quiz::choices(vocab, scope, rand::random(), connection)?;
+        if let Some(choices) = &choices {
+            StudySession::ask(user_id, &choices.sense.id, choices.by_word, &choices.options, connection)?;
+        }
+        Ok(choices)
+    })
+}
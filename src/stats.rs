@@ -0,0 +1,277 @@
+// How a learner is doing: cards finished per day, the streak, accuracy per book,
+// how well the words are known and how many reviews are coming. Days are UTC days.
+use rocket::request::FlashMessage;
+use rocket_contrib::databases::diesel::{PgConnection, QueryResult};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use maud::{html, Markup};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{app_page_view, db, db::*, flash_text, TEXT};
+use crate::types::ServerAcceptLangauge;
+
+/// Days of history and of forecast.
+pub const DAYS: usize = 30;
+/// Words reviewed again this many days after the last time are mature, as in Anki.
+pub const MATURE_INTERVAL_DAYS: i32 = 21;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookAccuracy {
+    /// `None` for every word
+    pub book_id: Option<Uuid>,
+    pub name: String,
+    pub passed: i64,
+    pub finished: i64,
+}
+
+impl BookAccuracy {
+    /// `None` before any card was finished.
+    pub fn percent(&self) -> Option<f64> {
+        if self.finished == 0 {
+            None
+        } else {
+            Some(self.passed as f64 * 100.0 / self.finished as f64)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CardCounts {
+    /// never studied
+    pub new: i64,
+    pub young: i64,
+    pub mature: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Progress {
+    /// days in a row with reviews, up to today or yesterday
+    pub streak_days: u32,
+    pub reviews_today: i64,
+    /// cards finished on each of the last `DAYS` days, today last
+    pub daily_reviews: Vec<DayCount>,
+    /// every word first, then each book
+    pub accuracy: Vec<BookAccuracy>,
+    pub cards: CardCounts,
+    /// cards due on each of the next `DAYS` days, overdue ones today
+    pub forecast: Vec<DayCount>,
+}
+
+impl Progress {
+    pub fn load(user_id: &Uuid, all_words: &str, now: DateTime<Utc>, connection: &PgConnection) -> QueryResult<Progress> {
+        let today = now.date_naive();
+        let first = today - Duration::days(DAYS as i64 - 1);
+        let finished = ReviewLog::finished_since(user_id, start_of(first), connection)?;
+        let daily_reviews = count_by_day(&finished, first, DAYS);
+
+        let (passed, total) = ReviewLog::accuracy(user_id, WordScope::AllWords, connection)?;
+        let mut accuracy = vec![BookAccuracy {
+            book_id: None,
+            name: all_words.to_string(),
+            passed,
+            finished: total,
+        }];
+        let mut by_book = ReviewLog::accuracy_by_book(user_id, connection)?;
+        for book in VocabBook::get_by_user(user_id, connection)? {
+            let (passed, total) = by_book.remove(&book.id).unwrap_or((0, 0));
+            accuracy.push(BookAccuracy {
+                book_id: Some(book.id),
+                name: book.name,
+                passed,
+                finished: total,
+            });
+        }
+
+        let studied = VocabProgress::count_for_user(user_id, connection)?;
+        let mature = VocabProgress::count_mature(user_id, MATURE_INTERVAL_DAYS, connection)?;
+        let cards = CardCounts {
+            new: (Vocab::count(connection)? - studied).max(0),
+            young: studied - mature,
+            mature,
+        };
+
+        let due = VocabProgress::due_before(user_id, start_of(today + Duration::days(DAYS as i64)), connection)?;
+        Ok(Progress {
+            streak_days: streak(&ReviewLog::days(user_id, connection)?, today),
+            reviews_today: daily_reviews.last().map_or(0, |d| d.count),
+            daily_reviews,
+            accuracy,
+            cards,
+            forecast: count_by_day(&due, today, DAYS),
+        })
+    }
+}
+
+fn start_of(day: NaiveDate) -> DateTime<Utc> {
+    day.and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
+
+/// Counts `times` on each of `days` days from `first` on, earlier times count on `first`.
+pub fn count_by_day(times: &[DateTime<Utc>], first: NaiveDate, days: usize) -> Vec<DayCount> {
+    let mut counts: Vec<DayCount> = (0..days)
+        .map(|i| DayCount {
+            date: first + Duration::days(i as i64),
+            count: 0,
+        })
+        .collect();
+    for time in times {
+        let i = (time.date_naive() - first).num_days().max(0) as usize;
+        if let Some(day) = counts.get_mut(i) {
+            day.count += 1;
+        }
+    }
+    counts
+}
+
+/// Consecutive days among `days`, latest first, ending today; a streak is not lost before today is over.
+pub fn streak(days: &[NaiveDate], today: NaiveDate) -> u32 {
+    let mut expected = if days.first() == Some(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+    let mut streak = 0;
+    for day in days.iter().skip_while(|day| **day > expected) {
+        if *day != expected {
+            break;
+        }
+        streak += 1;
+        expected = expected - Duration::days(1);
+    }
+    streak
+}
+
+#[get("/stats")]
+pub fn stats_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    conn: db::Connection,
+) -> Result<Markup> {
+    let flash_msg = flash_text(flash);
+    let progress = Progress::load(&user.id, TEXT[&lang]["book-all-words"], Utc::now(), &conn)?;
+    Ok(stats_view(lang, &user, &flash_msg, &progress))
+}
+
+fn stats_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    progress: &Progress,
+) -> Markup {
+    let text = &TEXT[&lang];
+    let overall = progress.accuracy.first().and_then(BookAccuracy::percent);
+    let due_today = progress.forecast.first().map_or(0, |d| d.count);
+    let cards = &progress.cards;
+    let total = (cards.new + cards.young + cards.mature).max(1);
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            h1 class="title" { (text["stats-title"]) }
+            nav class="level box" {
+                (level_item(text["stats-streak"], html! { (progress.streak_days) " " (text["stats-days"]) }))
+                (level_item(text["stats-reviews-today"], html! { (progress.reviews_today) }))
+                (level_item(text["stats-accuracy"], percent_view(overall)))
+                (level_item(text["stats-due-today"], html! { (due_today) }))
+            }
+            div class="columns" {
+                div class="column" {
+                    div class="box" {
+                        h2 class="subtitle" { (text["stats-daily-reviews"]) }
+                        (bars_view(&progress.daily_reviews, "has-background-primary"))
+                    }
+                }
+                div class="column" {
+                    div class="box" {
+                        h2 class="subtitle" { (text["stats-forecast"]) }
+                        (bars_view(&progress.forecast, "has-background-info"))
+                    }
+                }
+            }
+            div class="columns" {
+                div class="column" {
+                    div class="box" {
+                        h2 class="subtitle" { (text["stats-cards"]) }
+                        @for (key, count, color) in &[
+                            ("stats-mature", cards.mature, "is-success"),
+                            ("stats-young", cards.young, "is-warning"),
+                            ("stats-new", cards.new, "is-light"),
+                        ] {
+                            div class="level is-mobile mb-1" {
+                                div class="level-left" { span class="level-item" { (text[key]) } }
+                                div class="level-right" { span class="level-item" { (count) } }
+                            }
+                            progress class={"progress " (color)} value=(count) max=(total) {}
+                        }
+                    }
+                }
+                div class="column" {
+                    div class="box" {
+                        h2 class="subtitle" { (text["stats-accuracy"]) }
+                        table class="table is-fullwidth" {
+                            tbody {
+                                @for book in &progress.accuracy {
+                                    tr {
+                                        td { (book.name) }
+                                        td class="has-text-right" { (book.passed) " / " (book.finished) }
+                                        td class="has-text-right" { (percent_view(book.percent())) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn level_item(heading: &str, value: Markup) -> Markup {
+    html! {
+        div class="level-item has-text-centered" {
+            div {
+                p class="heading" { (heading) }
+                p class="title" { (value) }
+            }
+        }
+    }
+}
+
+fn percent_view(percent: Option<f64>) -> Markup {
+    html! {
+        @match percent {
+            Some(percent) => { (format!("{:.0}%", percent)) }
+            None => { "-" }
+        }
+    }
+}
+
+/// One bar per day, scaled to the busiest day.
+fn bars_view(days: &[DayCount], color: &str) -> Markup {
+    let max = days.iter().map(|d| d.count).max().unwrap_or(0).max(1);
+    html! {
+        div style="display: flex; align-items: flex-end; height: 8rem" {
+            @for day in days {
+                div class=(color)
+                    style={"flex: 1; margin: 0 1px; min-height: 1px; height: " (day.count * 100 / max) "%"}
+                    title={(day.date) ": " (day.count)} {}
+            }
+        }
+        div class="level is-mobile is-size-7 has-text-grey" {
+            @if let (Some(first), Some(last)) = (days.first(), days.last()) {
+                span class="level-left" { (first.date.format("%m-%d")) }
+                span class="level-right" { (last.date.format("%m-%d")) }
+            }
+        }
+    }
+}
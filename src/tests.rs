@@ -72,3 +72,1131 @@ fn pasting() {
     assert_eq!(download_paste(&client, &id_1), body_1);
     assert_eq!(download_paste(&client, &id_2), body_2);
 }
+
+// Helpers for the tests that need the postgres database of Rocket.toml, run them with `./dev test`.
+mod fixtures {
+    use crate::db::{self, User, Vocab};
+    use rocket_contrib::databases::diesel::{Connection, PgConnection};
+
+    /// A connection whose changes are rolled back when it is dropped.
+    pub fn connection() -> PgConnection {
+        let connection = db::establish().expect("no database");
+        connection.begin_test_transaction().unwrap();
+        connection
+    }
+
+    pub fn user(name: &str, connection: &PgConnection) -> User {
+        let email = format!("{}@example.com", name);
+        User::insert_with_password(name, &email, "correct horse", connection).unwrap()
+    }
+
+    pub fn words(words: &[&str], connection: &PgConnection) {
+        for word in words {
+            Vocab::insert_if_missing(word, connection).unwrap();
+        }
+    }
+}
+
+mod scheduler {
+    use crate::scheduler::{Grade, Schedule, DEFAULT_EASE_FACTOR};
+    use chrono::{DateTime, Duration, Utc};
+
+    fn morning() -> DateTime<Utc> {
+        "2021-05-01T08:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn intervals_grow_when_recalled() {
+        let now = morning();
+        let first = Schedule::new(now).review(Grade::Recalled, now);
+        assert_eq!(first.interval_days, 1);
+        assert_eq!(first.due_at, now + Duration::days(1));
+
+        let second = first.review(Grade::Recalled, first.due_at);
+        assert_eq!(second.interval_days, 6);
+
+        let third = second.review(Grade::Recalled, second.due_at);
+        assert_eq!(third.interval_days, (6.0 * second.ease_factor).round() as i32);
+        assert_eq!(third.repetitions, 3);
+        assert_eq!(third.lapses, 0);
+    }
+
+    #[test]
+    fn failing_resets_repetitions_and_lowers_ease() {
+        let now = morning();
+        let learned = Schedule::new(now)
+            .review(Grade::Recalled, now)
+            .review(Grade::Recalled, now);
+
+        let forgot = learned.review(Grade::Forgot, now);
+        assert_eq!(forgot.repetitions, 0);
+        assert_eq!(forgot.interval_days, 0);
+        assert_eq!(forgot.lapses, 1);
+        assert!(forgot.ease_factor < learned.ease_factor);
+        assert!(forgot.due_at < now + Duration::days(1));
+
+        // a brand new word that is not known yet is not counted as a lapse
+        let new = Schedule::new(now).review(Grade::Mistaken, now);
+        assert_eq!(new.lapses, 0);
+    }
+
+    #[test]
+    fn ease_factor_has_a_floor() {
+        let now = morning();
+        let mut schedule = Schedule::new(now);
+        for _ in 0..20 {
+            schedule = schedule.review(Grade::Forgot, now);
+        }
+        assert!(schedule.ease_factor >= 1.3);
+        assert!(schedule.ease_factor < DEFAULT_EASE_FACTOR);
+    }
+}
+
+mod mailer {
+    use crate::db::User;
+    use crate::mailer::{mail_file_name, Mailer, MemoryMailTransport, TokenPurpose};
+    use crate::types::ServerAcceptLangauge;
+    use chrono::{Duration, Utc};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn user() -> User {
+        User {
+            id: Uuid::new_v4(),
+            username: "alice".to_string(),
+            email: "alice@example.com".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            email_verified_at: None,
+            is_admin: false,
+        }
+    }
+
+    #[test]
+    fn verification_mail_carries_a_valid_token() {
+        let outbox = Arc::new(MemoryMailTransport::default());
+        let mailer = Mailer::new(Box::new(outbox.clone()), "noreply@example.com", "http://localhost:8000/", b"secret");
+        let user = user();
+        mailer.send_verification(&user, ServerAcceptLangauge::English).unwrap();
+
+        let sent = outbox.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, user.email);
+        let link = sent[0].body.lines().last().unwrap();
+        let token = link.strip_prefix("http://localhost:8000/verify?token=").unwrap();
+        assert_eq!(
+            mailer.verify_token(token, TokenPurpose::VerifyEmail, Utc::now()),
+            Some((user.id, 0))
+        );
+        assert_eq!(mailer.verify_token(token, TokenPurpose::ResetPassword, Utc::now()), None);
+    }
+
+    #[test]
+    fn mail_file_names_stay_in_the_mail_directory() {
+        assert_eq!(mail_file_name(1, "alice@example.com"), "1-alice@example.com.txt");
+        assert_eq!(mail_file_name(1, "../../etc/passwd@x"), "1-.._.._etc_passwd@x.txt");
+        assert_eq!(mail_file_name(1, "a\\b c@x"), "1-a_b_c@x.txt");
+    }
+
+    #[test]
+    fn tokens_expire_and_cannot_be_forged() {
+        let mailer = Mailer::new(Box::new(MemoryMailTransport::default()), "", "", b"secret");
+        let user_id = Uuid::new_v4();
+        let now = Utc::now();
+        let token = mailer.sign_token(TokenPurpose::ResetPassword, &user_id, now + Duration::hours(2), 42);
+
+        assert_eq!(mailer.verify_token(&token, TokenPurpose::ResetPassword, now), Some((user_id, 42)));
+        assert_eq!(mailer.verify_token(&token, TokenPurpose::ResetPassword, now + Duration::hours(3)), None);
+
+        let other = Mailer::new(Box::new(MemoryMailTransport::default()), "", "", b"another secret");
+        assert_eq!(other.verify_token(&token, TokenPurpose::ResetPassword, now), None);
+
+        let (_, signature) = token.split_at(token.find('.').unwrap());
+        let forged_payload = base64::encode_config(
+            format!("reset:{}:{}:42", Uuid::new_v4(), (now + Duration::hours(2)).timestamp()),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let forged = format!("{}{}", forged_payload, signature);
+        assert_eq!(mailer.verify_token(&forged, TokenPurpose::ResetPassword, now), None);
+    }
+}
+
+mod senses {
+    use crate::db::{SenseGroup, VocabDict};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn sense(partofspeech: &str, meaning: &str) -> VocabDict {
+        VocabDict {
+            id: Uuid::new_v4(),
+            vocab: "cheer".to_string(),
+            partofspeech: partofspeech.to_string(),
+            meaning: meaning.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn senses_are_grouped_by_part_of_speech_in_order() {
+        let senses = [sense("动词", "欢呼"), sense("名词", "欢呼声"), sense("动词", "鼓励")];
+        assert_eq!(
+            VocabDict::group_senses(&senses),
+            vec![
+                SenseGroup {
+                    partofspeech: "动词".to_string(),
+                    meanings: vec!["欢呼".to_string(), "鼓励".to_string()],
+                },
+                SenseGroup {
+                    partofspeech: "名词".to_string(),
+                    meanings: vec!["欢呼声".to_string()],
+                },
+            ]
+        );
+    }
+}
+
+mod import {
+    use crate::import::{decode, parse, read_limited, ImportFormat, ImportRow, RowProblem};
+    use crate::types::ServerAcceptLangauge;
+
+    fn row(word: &str, partofspeech: &str, meaning: &str, book: Option<&str>) -> ImportRow {
+        ImportRow {
+            word: word.to_string(),
+            partofspeech: partofspeech.to_string(),
+            meaning: meaning.to_string(),
+            book: book.map(str::to_string),
+            example: None,
+        }
+    }
+
+    #[test]
+    fn csv_rows_keep_their_line_numbers() {
+        let input = "word,partofspeech,meaning,book\ncheer,动词,欢呼,CET4\n,名词,空的,\nabandon, 动词 ,放弃,\n";
+        let (rows, errors) = parse(ImportFormat::Csv, input);
+        assert_eq!(
+            rows,
+            vec![
+                (2, row("cheer", "动词", "欢呼", Some("CET4"))),
+                (4, row("abandon", "动词", "放弃", None)),
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[test]
+    fn tsv_accepts_pos_for_partofspeech() {
+        let input = "word\tpos\tmeaning\ncheer\t名词\t欢呼声\n";
+        let (rows, errors) = parse(ImportFormat::Tsv, input);
+        assert_eq!(rows, vec![(2, row("cheer", "名词", "欢呼声", None))]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn json_entries_are_checked_one_by_one() {
+        let input = r#"[{"word": "cheer", "partofspeech": "动词", "meaning": "鼓励"}, {"word": "cheer"}]"#;
+        let (rows, errors) = parse(ImportFormat::Json, input);
+        assert_eq!(rows, vec![(1, row("cheer", "动词", "鼓励", None))]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+
+        let (rows, errors) = parse(ImportFormat::Json, "{}");
+        assert!(rows.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn examples_are_optional() {
+        let input = "word,partofspeech,meaning,example\ncheer,动词,欢呼,\"*Cheer* up!\"\ncheer,名词,欢呼声, \n";
+        let (rows, errors) = parse(ImportFormat::Csv, input);
+        let mut with_example = row("cheer", "动词", "欢呼", None);
+        with_example.example = Some("*Cheer* up!".to_string());
+        assert_eq!(rows, vec![(2, with_example), (3, row("cheer", "名词", "欢呼声", None))]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn problems_are_told_in_the_language_of_the_page() {
+        let input = "word,partofspeech,meaning\n,名词,空的\nabbey,名词,\n";
+        let (_, errors) = parse(ImportFormat::Csv, input);
+        let problems: Vec<_> = errors.iter().map(|e| e.problem.clone()).collect();
+        assert_eq!(problems, vec![RowProblem::WordEmpty, RowProblem::MeaningEmpty]);
+        assert_eq!(problems[0].text(&ServerAcceptLangauge::SimpliedChinese), "单词为空");
+        // the command line reads English
+        assert_eq!(problems[1].to_string(), "meaning is empty");
+        assert_eq!(RowProblem::WordTooLong.to_string(), "word is longer than 100 characters");
+    }
+
+    #[test]
+    fn uploads_that_are_not_utf8_name_the_line() {
+        assert_eq!(decode("word\ncheer".as_bytes().to_vec()).unwrap(), "word\ncheer");
+        // 欢呼 in GBK
+        let error = decode(b"word,meaning\ncheer,\xbb\xb6\xba\xf4\n".to_vec()).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.problem, RowProblem::NotUtf8);
+        // UTF-16 with a byte order mark
+        assert_eq!(decode(vec![0xff, 0xfe, b'w', 0]).unwrap_err().line, 1);
+        assert!(error.problem.text(&ServerAcceptLangauge::SimpliedChinese).contains("UTF-8"));
+    }
+
+    #[test]
+    fn oversized_uploads_are_refused_rather_than_cut_off() {
+        assert_eq!(read_limited(&b"word"[..], 4).unwrap(), Some(b"word".to_vec()));
+        assert_eq!(read_limited(&b"words"[..], 4).unwrap(), None);
+        assert_eq!(read_limited(&b""[..], 0).unwrap(), Some(Vec::new()));
+    }
+}
+
+mod anki {
+    use crate::anki::{read_apkg, read_text, write_apkg, AnkiNote};
+
+    fn note(deck: &str, word: &str, meaning: &str, audio: Option<&[u8]>) -> AnkiNote {
+        AnkiNote {
+            deck: deck.to_string(),
+            word: word.to_string(),
+            meaning: meaning.to_string(),
+            audio: audio.map(<[u8]>::to_vec),
+        }
+    }
+
+    #[test]
+    fn exported_decks_import_back() {
+        let notes = vec![
+            note("CET4", "cheer", "v. 欢呼\nn. 欢呼声", Some(b"ID3 cheer")),
+            note("CET4", "R&D", "研发", None),
+            note("托福", "abandon", "动词 放弃", Some(b"ID3 abandon")),
+        ];
+        let apkg = write_apkg(&notes, "2021-05-01T08:00:00Z".parse().unwrap()).unwrap();
+        let imported: Vec<AnkiNote> = read_apkg(&apkg).unwrap().into_iter().map(|(_, n)| n).collect();
+        assert_eq!(imported, notes);
+
+        let rows = imported[0].to_rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[1].partofspeech.as_str(), rows[1].meaning.as_str()), ("n.", "欢呼声"));
+        assert_eq!(rows[0].book.as_deref(), Some("CET4"));
+        assert_eq!(imported[1].to_rows()[0].partofspeech, "-");
+    }
+
+    #[test]
+    fn text_exports_strip_html_and_read_deck_columns() {
+        let input = "#separator:tab\n#html:true\n#deck column:3\n\
+                     cheer[sound:cheer.mp3]\t<b>v.</b> 欢呼<br>n. 欢呼声\tCET4\n\
+                     abandon\t放弃&nbsp;\t\n";
+        let (notes, errors) = read_text(input, "fallback");
+        assert!(errors.is_empty());
+        assert_eq!(
+            notes,
+            vec![
+                (4, note("CET4", "cheer", "v. 欢呼\nn. 欢呼声", None)),
+                (5, note("fallback", "abandon", "放弃", None)),
+            ]
+        );
+    }
+}
+
+mod audio {
+    use crate::audio::{byte_range, probe, read_zip, vocab_for_file, word_index, AudioFormat, Rejection};
+    use crate::types::ServerAcceptLangauge;
+
+    // MPEG1 layer III, 128kbps, 44.1kHz, stereo: 417 byte frames
+    fn mp3(frames: usize) -> Vec<u8> {
+        let mut bytes = b"ID3\x03\x00\x00\x00\x00\x00\x04TAG!".to_vec();
+        for _ in 0..frames {
+            bytes.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            bytes.extend_from_slice(&[0; 413]);
+        }
+        bytes
+    }
+
+    fn ogg_page(granule: i64, body: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\x00\x02".to_vec();
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 12]);
+        page.push(1);
+        page.push(body.len() as u8);
+        page.extend_from_slice(body);
+        page
+    }
+
+    #[test]
+    fn recordings_are_checked_by_content_and_length() {
+        let (format, duration) = probe(&mp3(39)).unwrap();
+        assert_eq!(format, AudioFormat::Mp3);
+        assert!((duration - 1.0).abs() < 0.05, "{}", duration);
+        // a minute of audio is not a pronunciation
+        assert!(probe(&mp3(39 * 60)).is_err());
+        assert_eq!(probe(b"word,meaning\ncheer,欢呼\n"), Err(Rejection::NotAudio));
+        assert_eq!(
+            Rejection::Length(60.04).text(&ServerAcceptLangauge::SimpliedChinese),
+            "时长 60.0 秒, 应为 0.1 到 15 秒"
+        );
+
+        let mut vorbis = b"\x01vorbis\x00\x00\x00\x00\x01".to_vec();
+        vorbis.extend_from_slice(&44100u32.to_le_bytes());
+        vorbis.extend_from_slice(&[0; 14]);
+        let mut ogg = ogg_page(0, &vorbis);
+        ogg.extend(ogg_page(66150, &[0; 8]));
+        assert_eq!(probe(&ogg), Ok((AudioFormat::Ogg, 1.5)));
+    }
+
+    #[test]
+    fn file_names_map_to_words() {
+        let words = word_index(vec!["ice cream".to_string(), "I".to_string(), "i".to_string(), "Paris".to_string()]);
+        assert_eq!(vocab_for_file("audio/Ice_Cream.mp3", &words), Some("ice cream"));
+        assert_eq!(vocab_for_file("I.ogg", &words), Some("I"));
+        assert_eq!(vocab_for_file("i.mp3", &words), Some("i"));
+        assert_eq!(vocab_for_file(" paris .mp3", &words), Some("Paris"));
+        assert_eq!(vocab_for_file("cheer.mp3", &words), None);
+    }
+
+    #[test]
+    fn ranges_are_clamped_to_the_recording() {
+        assert_eq!(byte_range("bytes=0-99", 1000), Some(Ok((0, 100))));
+        assert_eq!(byte_range("bytes=900-", 1000), Some(Ok((900, 1000))));
+        assert_eq!(byte_range("bytes=-100", 1000), Some(Ok((900, 1000))));
+        assert_eq!(byte_range("bytes=500-5000", 1000), Some(Ok((500, 1000))));
+        assert_eq!(byte_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(byte_range("bytes=-0", 1000), Some(Err(())));
+        // malformed or multiple ranges get the whole file
+        assert_eq!(byte_range("bytes=5-3", 1000), None);
+        assert_eq!(byte_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(byte_range("lines=1-2", 1000), None);
+    }
+
+    #[test]
+    fn zips_that_unpack_past_the_budget_are_rejected() {
+        use std::io::{Cursor, Write};
+        use zip::{write::FileOptions, ZipWriter};
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in &["cheer.mp3", "abandon.mp3"] {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(&[0; 1000]).unwrap();
+        }
+        let zip = zip.finish().unwrap().into_inner();
+        assert_eq!(read_zip(&zip, 2000).unwrap().len(), 2);
+        // the budget is shared by all the entries
+        let error = read_zip(&zip, 1999).unwrap_err();
+        assert_eq!(error.downcast_ref::<Rejection>(), Some(&Rejection::ZipTooBig));
+        assert_eq!(
+            read_zip(b"not a zip", 2000).unwrap_err().downcast_ref::<Rejection>(),
+            Some(&Rejection::NotZip)
+        );
+    }
+}
+
+mod api {
+    use crate::api::token_hash;
+    use crate::scheduler::Grade;
+
+    #[test]
+    fn grades_are_read_in_lowercase() {
+        let grades: Vec<Grade> = serde_json::from_str(r#"["forgot", "mistaken", "recalled"]"#).unwrap();
+        assert_eq!(grades, vec![Grade::Forgot, Grade::Mistaken, Grade::Recalled]);
+        assert!(serde_json::from_str::<Grade>(r#""Recalled""#).is_err());
+    }
+
+    #[test]
+    fn only_the_token_hash_is_kept() {
+        let hash = token_hash("secret");
+        assert_eq!(hash.len(), 32);
+        assert_eq!(hash, token_hash("secret"));
+        assert_ne!(hash, token_hash("secret "));
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn only_a_valid_bearer_token_authenticates() {
+        use super::fixtures;
+        use crate::api::{issue_token, ApiUser};
+        use rocket::http::Status;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("mobile", &connection);
+        let request = |password: &str| {
+            serde_json::from_value(serde_json::json!({"email": "MOBILE@example.com", "password": password})).unwrap()
+        };
+        let refused = issue_token(&request("wrong horse"), &connection).unwrap_err();
+        assert_eq!(refused.0, Status::Unauthorized);
+
+        let issued = serde_json::to_value(issue_token(&request("correct horse"), &connection).unwrap()).unwrap();
+        let token = issued["token"].as_str().unwrap();
+        let api = ApiUser::authenticate(Some(&format!("Bearer {}", token)), &connection).unwrap().unwrap();
+        assert_eq!(api.user.id, user.id);
+        assert_eq!(api.token_id.to_string(), issued["id"].as_str().unwrap());
+
+        for header in &[None, Some(token.to_string()), Some("Bearer ".to_string()), Some(format!("Bearer {}x", token))] {
+            assert!(ApiUser::authenticate(header.as_deref(), &connection).unwrap().is_none(), "{:?}", header);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn grades_are_recorded_and_counted_in_the_stats() {
+        use super::fixtures;
+        use crate::api::{record_grade, user_stats};
+        use crate::db::Vocab;
+        use chrono::{Duration, Utc};
+        use rocket::http::Status;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("grader", &connection);
+        fixtures::words(&["abacus"], &connection);
+        let now = Utc::now();
+        let stats = |now| serde_json::to_value(user_stats(&user.id, "All words", now, &connection).unwrap()).unwrap();
+        let counts = |stats: &serde_json::Value| (stats["studied"].clone(), stats["due"].clone(), stats["reviews_today"].clone());
+        assert_eq!(counts(&stats(now)), (0.into(), 0.into(), 0.into()));
+
+        let grade = |vocab: &str| {
+            serde_json::from_value(serde_json::json!({"vocab": vocab, "grade": "recalled", "latency_ms": 1200})).unwrap()
+        };
+        assert_eq!(record_grade(&user.id, &grade("abbey"), now, &connection).unwrap_err().0, Status::NotFound);
+        let progress = record_grade(&user.id, &grade("abacus"), now, &connection).unwrap();
+        assert_eq!(progress.repetitions, 1);
+        assert!(progress.due_at > now);
+        assert_eq!(Vocab::get("abacus", &connection).unwrap().unwrap().review_count, 1);
+
+        let after = stats(now);
+        assert_eq!(counts(&after), (1.into(), 0.into(), 1.into()));
+        // the rest of the stats page comes along
+        assert_eq!(after["streak_days"], 1);
+        assert_eq!(after["accuracy"][0], serde_json::json!({"book_id": null, "name": "All words", "passed": 1, "finished": 1}));
+        assert_eq!(stats(progress.due_at + Duration::seconds(1))["due"], 1);
+    }
+}
+
+mod session {
+    use std::str::FromStr;
+
+    use crate::scheduler::Grade;
+    use crate::session::{next, Step};
+    use crate::types::{CardState, ReviewAction, StudyMode};
+    use rocket::{http::RawStr, request::FromFormValue};
+
+    #[test]
+    fn answering_reveals_then_finishes() {
+        assert_eq!(next(StudyMode::Flashcard, CardState::Answering, ReviewAction::Know), Some(Step::Reveal(CardState::Checking)));
+        assert_eq!(next(StudyMode::Flashcard, CardState::Answering, ReviewAction::DontKnow), Some(Step::Reveal(CardState::Remembering)));
+        assert_eq!(next(StudyMode::Flashcard, CardState::Checking, ReviewAction::Right), Some(Step::Finish(Grade::Recalled)));
+        assert_eq!(next(StudyMode::Flashcard, CardState::Checking, ReviewAction::Wrong), Some(Step::Finish(Grade::Mistaken)));
+        assert_eq!(next(StudyMode::Flashcard, CardState::Remembering, ReviewAction::Right), Some(Step::Finish(Grade::Forgot)));
+        assert_eq!(next(StudyMode::Flashcard, CardState::Remembering, ReviewAction::Wrong), Some(Step::Finish(Grade::Forgot)));
+    }
+
+    #[test]
+    fn buttons_not_on_the_page_are_rejected() {
+        assert_eq!(next(StudyMode::Flashcard, CardState::Answering, ReviewAction::Right), None);
+        assert_eq!(next(StudyMode::Flashcard, CardState::Checking, ReviewAction::Know), None);
+        assert_eq!(next(StudyMode::Flashcard, CardState::Remembering, ReviewAction::DontKnow), None);
+        assert_eq!(next(StudyMode::Flashcard, CardState::Reviewing, ReviewAction::Know), None);
+    }
+
+    #[test]
+    fn typed_answers_are_reviewed_before_the_next_word() {
+        // the answer is typed instead of pressing "知道", giving up still shows the meanings
+        assert_eq!(next(StudyMode::Typing, CardState::Answering, ReviewAction::Know), None);
+        assert_eq!(next(StudyMode::Dictation, CardState::Answering, ReviewAction::Know), None);
+        assert_eq!(next(StudyMode::Dictation, CardState::Answering, ReviewAction::DontKnow), Some(Step::Reveal(CardState::Remembering)));
+        assert_eq!(next(StudyMode::Typing, CardState::Answering, ReviewAction::DontKnow), Some(Step::Reveal(CardState::Remembering)));
+        assert_eq!(next(StudyMode::Typing, CardState::Reviewing, ReviewAction::Right), Some(Step::Next));
+    }
+
+    #[test]
+    fn choice_falls_back_to_flashcards() {
+        assert_eq!(next(StudyMode::Choice, CardState::Answering, ReviewAction::Know), Some(Step::Reveal(CardState::Checking)));
+        assert_eq!(next(StudyMode::Choice, CardState::Reviewing, ReviewAction::Wrong), Some(Step::Next));
+    }
+
+    #[test]
+    fn card_states_round_trip_and_reject_unknown_values() {
+        use strum::IntoEnumIterator;
+        for state in CardState::iter() {
+            assert_eq!(CardState::from_str(state.as_str()), Ok(state));
+            assert_eq!(CardState::from_form_value(RawStr::from_str(state.as_str())), Ok(state));
+        }
+        assert_eq!(CardState::from_form_value(RawStr::from_str("to_check")), Ok(CardState::Checking));
+        assert!(CardState::from_form_value(RawStr::from_str("to_guess")).is_err());
+        assert!(CardState::from_form_value(RawStr::from_str("")).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn presses_are_logged_and_finished_cards_graded() {
+        use super::fixtures;
+        use crate::db::{ReviewLog, StudySession, Vocab, VocabProgress};
+        use crate::session::press;
+        use chrono::Utc;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("presser", &connection);
+        fixtures::words(&["abacus"], &connection);
+        let now = Utc::now();
+        StudySession::get_or_start(&user.id, &connection).unwrap();
+        let answering = StudySession::update(&user.id, CardState::Answering, Some("abacus"), now, &connection).unwrap();
+
+        // a page of another card, or of this card in another state, moves nothing
+        assert!(!press(&user.id, &uuid::Uuid::new_v4(), ReviewAction::Know, now, &connection).unwrap());
+        assert!(!press(&user.id, &answering.token, ReviewAction::Right, now, &connection).unwrap());
+        assert!(ReviewLog::get_by_word(&user.id, "abacus", 10, &connection).unwrap().is_empty());
+
+        assert!(press(&user.id, &answering.token, ReviewAction::Know, now, &connection).unwrap());
+        let checking = StudySession::get_or_start(&user.id, &connection).unwrap();
+        assert_eq!(checking.card_state(), CardState::Checking);
+        // pressed twice, e.g. from another tab
+        assert!(!press(&user.id, &answering.token, ReviewAction::Know, now, &connection).unwrap());
+        assert!(press(&user.id, &checking.token, ReviewAction::Right, now, &connection).unwrap());
+        let session = StudySession::get_or_start(&user.id, &connection).unwrap();
+        assert_eq!((session.card_state(), session.vocab), (CardState::Answering, None));
+
+        let logs = ReviewLog::get_by_word(&user.id, "abacus", 10, &connection).unwrap();
+        let mut logged: Vec<_> = logs.iter().map(|log| (log.action.as_str(), log.passed)).collect();
+        logged.sort();
+        assert_eq!(logged, vec![("know", None), ("right", Some(true))]);
+        let progress = VocabProgress::get(&user.id, "abacus", &connection).unwrap().unwrap();
+        assert_eq!(progress.repetitions, 1);
+        // only the finished card ranks the word
+        assert_eq!(Vocab::get("abacus", &connection).unwrap().unwrap().review_count, 1);
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn switching_modes_keeps_a_revealed_card_to_grade() {
+        use super::fixtures;
+        use crate::db::StudySession;
+        use crate::session::{answer, press};
+        use chrono::Utc;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("switcher", &connection);
+        fixtures::words(&["abacus"], &connection);
+        let now = Utc::now();
+        StudySession::set_mode(&user.id, StudyMode::Typing, &connection).unwrap();
+        let typing = StudySession::update(&user.id, CardState::Answering, Some("abacus"), now, &connection).unwrap();
+        assert!(press(&user.id, &typing.token, ReviewAction::DontKnow, now, &connection).unwrap());
+
+        let switched = StudySession::set_mode(&user.id, StudyMode::Flashcard, &connection).unwrap();
+        assert_eq!(switched.card_state(), CardState::Remembering);
+        assert_eq!(switched.vocab.as_deref(), Some("abacus"));
+        // the typing form of the old page is out of date
+        assert!(!answer(&user.id, &typing.token, "abacus", now, &connection).unwrap());
+        assert!(press(&user.id, &switched.token, ReviewAction::Wrong, now, &connection).unwrap());
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn choices_are_kept_and_only_the_offered_ones_graded() {
+        use super::fixtures;
+        use crate::db::{NewVocabDict, ReviewLog, StudySession, VocabDict, WordScope};
+        use crate::session::{answer, choices};
+        use chrono::Utc;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("chooser", &connection);
+        let senses = [
+            ("abacus", "counting frame"),
+            ("abacus", "slab on top of a column"),
+            ("ledger", "book of accounts"),
+            ("tally", "running count"),
+            ("quill", "feather pen"),
+            ("slate", "writing tablet"),
+        ];
+        for &(vocab, meaning) in senses.iter() {
+            fixtures::words(&[vocab], &connection);
+            let sense = NewVocabDict { vocab, partofspeech: "n.", meaning };
+            VocabDict::insert_if_missing(&sense, &connection).unwrap();
+        }
+        let now = Utc::now();
+        StudySession::set_mode(&user.id, StudyMode::Choice, &connection).unwrap();
+        let session = StudySession::update(&user.id, CardState::Answering, Some("abacus"), now, &connection).unwrap();
+
+        let asked = choices(&user.id, WordScope::AllWords, &connection).unwrap().unwrap();
+        // reloading the page asks the same
+        assert_eq!(choices(&user.id, WordScope::AllWords, &connection).unwrap(), Some(asked.clone()));
+
+        // the other meaning of the word is right too, but was not offered
+        let other = if asked.sense.meaning == "counting frame" { "slab on top of a column" } else { "counting frame" };
+        assert!(!answer(&user.id, &session.token, other, now, &connection).unwrap());
+        assert!(!answer(&user.id, &session.token, "made up", now, &connection).unwrap());
+        assert!(ReviewLog::get_by_word(&user.id, "abacus", 10, &connection).unwrap().is_empty());
+
+        let wrong = asked.options.iter().find(|option| option.as_str() != asked.answer()).unwrap();
+        assert!(answer(&user.id, &session.token, wrong, now, &connection).unwrap());
+        let logs = ReviewLog::get_by_word(&user.id, "abacus", 10, &connection).unwrap();
+        assert_eq!(logs.iter().map(|log| log.passed).collect::<Vec<_>>(), vec![Some(false)]);
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn dictation_only_draws_words_with_a_recording() {
+        use super::fixtures;
+        use crate::db::{NewVocabSpeech, VocabBook, VocabBookContent, VocabProgress, VocabSpeech, WordScope};
+        use chrono::Utc;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("listener", &connection);
+        fixtures::words(&["abacus", "ledger"], &connection);
+        let book = VocabBook::insert("dictation", &user.id, &connection).unwrap();
+        VocabBookContent::insert(&book.id, "abacus", &connection).unwrap();
+        VocabBookContent::insert(&book.id, "ledger", &connection).unwrap();
+        let speech = NewVocabSpeech { vocab: "ledger", mp3: b"ID3", format: "mp3" };
+        VocabSpeech::insert_if_missing(&speech, &connection).unwrap();
+        let scope = WordScope::Book(&book.id);
+        let now = Utc::now();
+
+        assert_eq!(VocabProgress::next_due(&user.id, scope, true, now, &connection).unwrap().as_deref(), Some("ledger"));
+        VocabBookContent::delete(&book.id, "ledger", &connection).unwrap();
+        assert_eq!(VocabProgress::next_due(&user.id, scope, true, now, &connection).unwrap(), None);
+        assert_eq!(VocabProgress::next_due(&user.id, scope, false, now, &connection).unwrap().as_deref(), Some("abacus"));
+    }
+}
+
+mod quiz {
+    use crate::db::VocabDict;
+    use crate::quiz::{add_distractors, check, diff, normalize, Edit, Verdict, CHOICE_COUNT};
+    use crate::scheduler::Grade;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn sense(vocab: &str, meaning: &str) -> VocabDict {
+        VocabDict {
+            id: Uuid::new_v4(),
+            vocab: vocab.to_string(),
+            partofspeech: "n.".to_string(),
+            meaning: meaning.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn case_accents_and_spaces_do_not_matter() {
+        assert_eq!(normalize("  Café  au\tLait "), "cafe au lait");
+        assert_eq!(normalize("naïve"), "naive");
+        assert_eq!(check("café", "CAFE"), Verdict::Exact);
+        assert_eq!(check("ice cream", "ice  cream"), Verdict::Exact);
+    }
+
+    #[test]
+    fn longer_words_forgive_typos() {
+        assert_eq!(check("cat", "cut"), Verdict::Wrong);
+        assert_eq!(check("apple", "aple"), Verdict::Close);
+        assert_eq!(check("necessary", "neccesary"), Verdict::Close);
+        assert_eq!(check("necessary", "nesesery"), Verdict::Wrong);
+        assert_eq!(check("apple", ""), Verdict::Wrong);
+        assert_eq!(Verdict::Close.grade(), Grade::Recalled);
+        assert_eq!(Verdict::Wrong.grade(), Grade::Mistaken);
+    }
+
+    #[test]
+    fn diff_marks_each_mistake() {
+        assert_eq!(
+            diff("apple", "aplle"),
+            vec![
+                Edit::Same('a'),
+                Edit::Same('p'),
+                Edit::Replaced { expected: 'p', typed: 'l' },
+                Edit::Same('l'),
+                Edit::Same('e'),
+            ]
+        );
+        assert_eq!(diff("cat", "cats"), vec![Edit::Same('c'), Edit::Same('a'), Edit::Same('t'), Edit::Extra('s')]);
+        assert_eq!(diff("cat", "ct"), vec![Edit::Same('c'), Edit::Missing('a'), Edit::Same('t')]);
+    }
+
+    #[test]
+    fn distractors_are_never_also_right() {
+        let senses = vec![sense("car", "汽车"), sense("car", "车厢")];
+        let like = vec![
+            sense("auto", "汽车"),
+            sense("bus", "公共汽车"),
+            sense("coach", "公共汽车"),
+            sense("train", "火车"),
+            sense("bike", "自行车"),
+            sense("boat", "船"),
+        ];
+        let mut meanings = vec!["汽车".to_string()];
+        add_distractors(&mut meanings, true, &senses, like.clone());
+        assert_eq!(meanings, vec!["汽车", "公共汽车", "火车", "自行车"]);
+
+        // a synonym is not offered as a wrong word either
+        let mut words = vec!["car".to_string()];
+        add_distractors(&mut words, false, &senses, like);
+        assert_eq!(words.len(), CHOICE_COUNT);
+        assert_eq!(words, vec!["car", "bus", "coach", "train"]);
+    }
+}
+
+mod stats {
+    use crate::stats::{count_by_day, streak, DayCount};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    #[test]
+    fn counts_fall_on_their_day() {
+        let times = vec![
+            Utc.with_ymd_and_hms(2026, 10, 1, 8, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 10, 3, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 10, 3, 23, 59, 59).unwrap(),
+            Utc.with_ymd_and_hms(2026, 10, 9, 12, 0, 0).unwrap(),
+        ];
+        let counts = count_by_day(&times, day(2), 3);
+        // overdue ones count on the first day, those after the last one are left out
+        assert_eq!(
+            counts,
+            vec![
+                DayCount { date: day(2), count: 1 },
+                DayCount { date: day(3), count: 2 },
+                DayCount { date: day(4), count: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn streak_survives_until_the_day_is_over() {
+        assert_eq!(streak(&[day(18), day(17), day(16), day(14)], day(18)), 3);
+        assert_eq!(streak(&[day(17), day(16), day(14)], day(18)), 2);
+        assert_eq!(streak(&[day(16), day(15)], day(18)), 0);
+        assert_eq!(streak(&[], day(18)), 0);
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn only_finished_cards_count_per_book() {
+        use super::fixtures;
+        use crate::db::{ReviewLog, VocabBook, VocabBookContent};
+        use crate::scheduler::Grade;
+        use crate::types::ReviewAction;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("counter", &connection);
+        fixtures::words(&["abacus", "ledger"], &connection);
+        let book = VocabBook::insert("counted", &user.id, &connection).unwrap();
+        let untouched = VocabBook::insert("untouched", &user.id, &connection).unwrap();
+        VocabBookContent::insert(&book.id, "abacus", &connection).unwrap();
+        VocabBookContent::insert(&book.id, "ledger", &connection).unwrap();
+
+        // a press that only reveals the meanings is not a review
+        ReviewLog::insert(&user.id, "abacus", ReviewAction::Know, None, 900, &connection).unwrap();
+        assert!(ReviewLog::days(&user.id, &connection).unwrap().is_empty());
+        assert!(ReviewLog::accuracy_by_book(&user.id, &connection).unwrap().is_empty());
+
+        ReviewLog::insert(&user.id, "abacus", ReviewAction::Right, Some(Grade::Recalled), 900, &connection).unwrap();
+        ReviewLog::insert(&user.id, "ledger", ReviewAction::Wrong, Some(Grade::Mistaken), 900, &connection).unwrap();
+        assert_eq!(ReviewLog::days(&user.id, &connection).unwrap(), vec![Utc::now().date_naive()]);
+        let by_book = ReviewLog::accuracy_by_book(&user.id, &connection).unwrap();
+        assert_eq!(by_book.get(&book.id), Some(&(1, 2)));
+        assert_eq!(by_book.get(&untouched.id), None);
+    }
+}
+
+mod words {
+    use crate::types::PanelRankType;
+    use crate::db::escape_like;
+    use crate::words::back_url;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn rank_round_trips_through_the_cookie() {
+        for rank in PanelRankType::iter() {
+            let value: &'static str = rank.into();
+            assert_eq!(PanelRankType::from(value), rank);
+        }
+    }
+
+    #[test]
+    fn unknown_rank_is_most_recent() {
+        assert_eq!(PanelRankType::from("Most Popular"), PanelRankType::MostRecent);
+        assert_eq!(PanelRankType::from(""), PanelRankType::MostRecent);
+    }
+
+    #[test]
+    fn likes_only_come_back_to_this_site() {
+        assert_eq!(back_url("/words?page=2"), "/words?page=2");
+        assert_eq!(back_url("/books/favorites"), "/books/favorites");
+        assert_eq!(back_url("//evil.example/"), "/");
+        assert_eq!(back_url("/\\evil.example/"), "/");
+        assert_eq!(back_url("https://evil.example/"), "/");
+        assert_eq!(back_url(""), "/");
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("ab"), "ab");
+        assert_eq!(escape_like("100%_a\\b"), "100\\%\\_a\\\\b");
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn search_puts_the_word_then_prefixes_then_meanings() {
+        use super::fixtures;
+        use crate::db::{NewVocabDict, Vocab, VocabDict};
+
+        let connection = fixtures::connection();
+        fixtures::words(&["zyxqb", "zyxq", "zyxqa", "quorp", "azyxq"], &connection);
+        let sense = NewVocabDict { vocab: "quorp", partofspeech: "n.", meaning: "a zyxq of sorts" };
+        VocabDict::insert_if_missing(&sense, &connection).unwrap();
+        let search = |query, page| {
+            let (words, pages) = Vocab::search(query, page, 2, &connection).unwrap();
+            (words.into_iter().map(|w| w.vocab).collect::<Vec<_>>(), pages)
+        };
+
+        // a word containing the query somewhere else is not found
+        assert_eq!(search("zyxq", 1), (vec!["zyxq".to_string(), "zyxqa".to_string()], 2));
+        assert_eq!(search("zyxq", 2), (vec!["zyxqb".to_string(), "quorp".to_string()], 2));
+        assert_eq!(search("zyxq", 3).0, Vec::<String>::new());
+        assert_eq!(search("ZYXQA", 1).0, vec!["zyxqa".to_string()]);
+        // wildcards are matched literally
+        assert_eq!(search("zyx_", 1).0, Vec::<String>::new());
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn an_unknown_word_has_no_page() {
+        use super::fixtures;
+        use crate::words::WordPage;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("reader", &connection);
+        assert!(WordPage::load(&user.id, "zyxqnope", &connection).unwrap().is_none());
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn words_are_added_once_and_only_to_own_books() {
+        use super::fixtures;
+        use crate::db::{VocabBook, VocabBookContent};
+        use crate::words::{add_word_to_book, WordPage};
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("collector", &connection);
+        let other = fixtures::user("stranger", &connection);
+        fixtures::words(&["abacus"], &connection);
+        let mine = VocabBook::insert("mine", &user.id, &connection).unwrap();
+        let spare = VocabBook::insert("spare", &user.id, &connection).unwrap();
+        let theirs = VocabBook::insert("theirs", &other.id, &connection).unwrap();
+
+        assert!(!add_word_to_book(&user.id, "abacus", &theirs.id, &connection).unwrap());
+        assert!(!add_word_to_book(&user.id, "zyxqnope", &mine.id, &connection).unwrap());
+        assert_eq!(VocabBookContent::count_by_book(&theirs.id, &connection).unwrap(), 0);
+
+        assert!(add_word_to_book(&user.id, "abacus", &mine.id, &connection).unwrap());
+        assert!(add_word_to_book(&user.id, "abacus", &mine.id, &connection).unwrap());
+        assert_eq!(VocabBookContent::count_by_book(&mine.id, &connection).unwrap(), 1);
+
+        // their book with the word is not listed
+        VocabBookContent::insert(&theirs.id, "abacus", &connection).unwrap();
+        let containing = VocabBook::containing(&user.id, "abacus", &connection).unwrap();
+        assert_eq!(containing.iter().map(|b| b.id).collect::<Vec<_>>(), vec![mine.id]);
+        let page = WordPage::load(&user.id, "abacus", &connection).unwrap().unwrap();
+        let (with, without) = page.books;
+        assert_eq!(with.iter().map(|b| b.id).collect::<Vec<_>>(), vec![mine.id]);
+        assert_eq!(without.iter().map(|b| b.id).collect::<Vec<_>>(), vec![spare.id]);
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn history_is_the_users_own_and_limited() {
+        use super::fixtures;
+        use crate::db::ReviewLog;
+        use crate::types::ReviewAction;
+
+        let connection = fixtures::connection();
+        let user = fixtures::user("historian", &connection);
+        let other = fixtures::user("bystander", &connection);
+        fixtures::words(&["abacus", "ledger"], &connection);
+        for _ in 0..3 {
+            ReviewLog::insert(&user.id, "abacus", ReviewAction::Know, None, 900, &connection).unwrap();
+        }
+        ReviewLog::insert(&user.id, "ledger", ReviewAction::Know, None, 900, &connection).unwrap();
+        ReviewLog::insert(&other.id, "abacus", ReviewAction::Know, None, 900, &connection).unwrap();
+
+        let history = ReviewLog::get_by_word(&user.id, "abacus", 2, &connection).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|log| log.user_id == user.id && log.vocab == "abacus"));
+        assert_eq!(ReviewLog::get_by_word(&user.id, "abacus", 10, &connection).unwrap().len(), 3);
+    }
+}
+
+mod notes {
+    use super::fixtures;
+    use crate::db::{User, VocabNote};
+    use crate::notes::{save, Saved, MAX_NOTE_LEN};
+    use rocket_contrib::databases::diesel::PgConnection;
+
+    fn body(user: &User, vocab: &str, connection: &PgConnection) -> Option<String> {
+        VocabNote::get(&user.id, vocab, connection).unwrap().map(|note| note.body)
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn saving_again_replaces_the_note() {
+        let connection = fixtures::connection();
+        let user = fixtures::user("noter", &connection);
+        fixtures::words(&["abacus"], &connection);
+
+        assert_eq!(save(&user.id, "abacus", "  beads on wires ", &connection).unwrap(), Saved::Written);
+        assert_eq!(save(&user.id, "abacus", "counting frame", &connection).unwrap(), Saved::Written);
+        assert_eq!(body(&user, "abacus", &connection).as_deref(), Some("counting frame"));
+
+        let long = "a".repeat(MAX_NOTE_LEN + 1);
+        assert_eq!(save(&user.id, "abacus", &long, &connection).unwrap(), Saved::TooLong);
+        assert_eq!(save(&user.id, "zyxqnope", "hmm", &connection).unwrap(), Saved::UnknownWord);
+        assert_eq!(body(&user, "abacus", &connection).as_deref(), Some("counting frame"));
+    }
+
+    #[test]
+    #[ignore = "needs the postgres database"]
+    fn a_blank_note_deletes_only_ones_own() {
+        let connection = fixtures::connection();
+        let user = fixtures::user("eraser", &connection);
+        let other = fixtures::user("keeper", &connection);
+        fixtures::words(&["abacus"], &connection);
+        save(&user.id, "abacus", "mine", &connection).unwrap();
+        save(&other.id, "abacus", "theirs", &connection).unwrap();
+        assert_eq!(body(&user, "abacus", &connection).as_deref(), Some("mine"));
+
+        assert_eq!(save(&user.id, "abacus", " \n ", &connection).unwrap(), Saved::Deleted);
+        assert_eq!(body(&user, "abacus", &connection), None);
+        assert_eq!(body(&other, "abacus", &connection).as_deref(), Some("theirs"));
+        // deleting a note that is not there is fine
+        assert_eq!(save(&user.id, "abacus", "", &connection).unwrap(), Saved::Deleted);
+    }
+}
+
+mod markdown {
+    use crate::{highlight_spans, makrdown_parse_clean};
+
+    #[test]
+    fn the_word_is_found_at_word_starts_in_any_case() {
+        assert_eq!(
+            highlight_spans("Cheer up, cheers! Not in overcheer.", "cheer"),
+            vec![
+                ("Cheer", true),
+                (" up, ", false),
+                ("cheer", true),
+                ("s! Not in overcheer.", false),
+            ]
+        );
+        assert_eq!(highlight_spans("欢呼", "cheer"), vec![("欢呼", false)]);
+        assert_eq!(highlight_spans("cheer", ""), vec![("cheer", false)]);
+    }
+
+    #[test]
+    fn examples_are_marked_and_cleaned() {
+        assert_eq!(
+            makrdown_parse_clean("*They* cheered.<script>alert(1)</script>", Some("cheer")),
+            "<p><em>They</em> <mark>cheer</mark>ed.</p>\n"
+        );
+        // nothing is marked inside code or link targets
+        assert_eq!(
+            makrdown_parse_clean("`cheer` [cheer](/word/cheer)", Some("cheer")),
+            "<p><code>cheer</code> <a href=\"/word/cheer\" rel=\"noopener noreferrer\"><mark>cheer</mark></a></p>\n"
+        );
+        assert_eq!(makrdown_parse_clean("cheer", None), "<p>cheer</p>\n");
+    }
+
+    #[test]
+    fn images_are_dropped() {
+        assert_eq!(
+            makrdown_parse_clean("![cheer](https://tracker.example/p.png) up <img src=x onerror=alert(1)>", None),
+            "<p> up </p>\n"
+        );
+    }
+}
+
+mod i18n {
+    use crate::i18n::{fill, parse, with_fallback, ENGLISH, LOCALES, TEXT};
+    use crate::types::ServerAcceptLangauge;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn every_locale_has_the_english_keys() {
+        let english: BTreeSet<_> = parse(ENGLISH).unwrap().keys().copied().collect();
+        for (lang, source) in LOCALES {
+            let keys: BTreeSet<_> = parse(source).unwrap().keys().copied().collect();
+            let missing: Vec<_> = english.difference(&keys).collect();
+            let unknown: Vec<_> = keys.difference(&english).collect();
+            assert!(missing.is_empty(), "{} misses {:?}", lang, missing);
+            assert!(unknown.is_empty(), "{} has unknown {:?}", lang, unknown);
+        }
+        assert_eq!(TEXT.len(), 3);
+        assert_eq!(TEXT[&ServerAcceptLangauge::Japananese]["button-next"], "次へ");
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_english() {
+        let english = parse("a = \"A\"\nb = \"B\"").unwrap();
+        let strings = with_fallback(parse("a = \"甲\"").unwrap(), &english);
+        assert_eq!((strings["a"], strings["b"]), ("甲", "B"));
+    }
+
+    #[test]
+    fn arguments_go_where_the_text_puts_them() {
+        assert_eq!(fill("{}s long, expected {}s to {}s", &[&"1.5", &0.1, &15]), "1.5s long, expected 0.1s to 15s");
+        assert_eq!(fill("{} 已经用了另一个文件", &[&"cheer"]), "cheer 已经用了另一个文件");
+        // a locale may leave one out
+        assert_eq!(fill("no word", &[&"cheer"]), "no word");
+    }
+}
+
+mod lang {
+    use crate::types::ServerAcceptLangauge::{self, English, Japananese, SimpliedChinese};
+
+    #[test]
+    fn browser_headers_pick_a_supported_language() {
+        let negotiate = ServerAcceptLangauge::negotiate;
+        // Chrome, Firefox and Safari
+        assert_eq!(negotiate("zh-CN,zh;q=0.9,en;q=0.8,en-GB;q=0.7,en-US;q=0.6"), Some(SimpliedChinese));
+        assert_eq!(negotiate("ja,en-US;q=0.7,en;q=0.3"), Some(Japananese));
+        assert_eq!(negotiate("en-US,en;q=0.9"), Some(English));
+        assert_eq!(negotiate("zh-TW,zh-Hant;q=0.9"), Some(SimpliedChinese));
+        // unsupported ones are skipped
+        assert_eq!(negotiate("fr-FR,fr;q=0.9,ja;q=0.8,en;q=0.7"), Some(Japananese));
+        assert_eq!(negotiate("de-DE,de;q=0.9"), None);
+        assert_eq!(negotiate(""), None);
+    }
+
+    #[test]
+    fn quality_wins_over_order() {
+        let negotiate = ServerAcceptLangauge::negotiate;
+        assert_eq!(negotiate("en;q=0.5, ZH-cn;q=0.8"), Some(SimpliedChinese));
+        assert_eq!(negotiate("en;q=0.8, ja;Q=0.8"), Some(English));
+        assert_eq!(negotiate("zh;q=0, ja;q=0.1"), Some(Japananese));
+        // malformed qualities drop the range
+        assert_eq!(negotiate("zh;q=abc, ja;q=2, en;q=0.1"), Some(English));
+        // "jp" is not a language tag
+        assert_eq!(negotiate("jp"), None);
+    }
+
+    #[test]
+    fn parameters_that_are_not_ascii_are_ignored() {
+        let negotiate = ServerAcceptLangauge::negotiate;
+        assert_eq!(negotiate("en;中=1"), Some(English));
+        assert_eq!(negotiate("ja;q中=1, zh;q=0.5"), Some(Japananese));
+        assert_eq!(negotiate("zh;q=中, ja;q=0.5"), Some(Japananese));
+        assert_eq!(negotiate("中文;q=1, en;q=0.5"), Some(English));
+    }
+
+    #[test]
+    fn wildcard_is_any_language_not_refused() {
+        let negotiate = ServerAcceptLangauge::negotiate;
+        assert_eq!(negotiate("*"), Some(English));
+        assert_eq!(negotiate("de, *;q=0.5"), Some(English));
+        assert_eq!(negotiate("en;q=0, *"), Some(SimpliedChinese));
+    }
+
+    #[test]
+    fn choices_round_trip() {
+        for lang in &ServerAcceptLangauge::ALL {
+            assert_eq!(ServerAcceptLangauge::from(lang.to_string().as_str()), *lang);
+        }
+        assert_eq!(Japananese.to_string(), "ja");
+        // cookies of earlier versions
+        assert_eq!(ServerAcceptLangauge::from("jp"), Japananese);
+        assert_eq!(ServerAcceptLangauge::from("klingon"), English);
+    }
+}
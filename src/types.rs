@@ -1,9 +1,9 @@
-use std::{fmt, str::FromStr};
+use std::{cmp::Ordering, fmt, str::FromStr};
 
 use rocket::{
     http::RawStr,
     request,
-    request::{FromParam, FromRequest, Request},
+    request::{FromFormValue, FromParam, FromRequest, Request},
     Outcome,
 };
 
@@ -21,9 +21,104 @@ pub enum PanelRankType {
     MostLike,
 }
 
+impl Default for PanelRankType {
+    fn default() -> Self {
+        PanelRankType::MostRecent
+    }
+}
+
+/// An unknown value, e.g. from an old cookie, is the default order.
 impl From<&str> for PanelRankType {
     fn from(s: &str) -> Self {
-        PanelRankType::from_str(s).unwrap()
+        PanelRankType::from_str(s).unwrap_or_default()
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for PanelRankType {
+    type Error = ();
+
+    /// The order chosen with /api/order.
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let rank = request
+            .cookies()
+            .get("state_choosen_rank")
+            .map_or(PanelRankType::default(), |c| c.value().into());
+        Outcome::Success(rank)
+    }
+}
+
+// stored as text in review_logs.action
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, IntoStaticStr, EnumString, EnumIter)]
+pub enum ReviewAction {
+    #[strum(serialize = "know")]
+    Know,
+    #[strum(serialize = "dontknow")]
+    DontKnow,
+    #[strum(serialize = "right")]
+    Right,
+    #[strum(serialize = "wrong")]
+    Wrong,
+}
+
+/// Where the card on the study page is, stored as text in study_sessions.state.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, IntoStaticStr, EnumString, EnumIter)]
+pub enum CardState {
+    /// only the word is shown, waiting for "知道" or "不知道"
+    #[strum(serialize = "to_answer")]
+    Answering,
+    /// "知道": the meanings are shown to check the answer
+    #[strum(serialize = "to_check")]
+    Checking,
+    /// "不知道": the meanings are shown to remember them
+    #[strum(serialize = "to_remember")]
+    Remembering,
+    /// the typed answer was graded, its mistakes are shown
+    #[strum(serialize = "to_review")]
+    Reviewing,
+}
+
+impl CardState {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+impl<'v> FromFormValue<'v> for CardState {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        CardState::from_str(form_value.as_str()).map_err(|_| form_value)
+    }
+}
+
+/// How the study page asks for a word, stored as text in study_sessions.mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, IntoStaticStr, EnumString, EnumIter)]
+pub enum StudyMode {
+    /// the learner says whether they knew the word
+    #[strum(serialize = "flashcard")]
+    Flashcard,
+    /// the meanings are shown and the learner types the word
+    #[strum(serialize = "typing")]
+    Typing,
+    /// the learner picks the meaning of the word, or the word of a meaning, out of four
+    #[strum(serialize = "choice")]
+    Choice,
+    /// the recording is played and the learner types the word, words without one are skipped
+    #[strum(serialize = "dictation")]
+    Dictation,
+}
+
+impl StudyMode {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+impl<'v> FromFormValue<'v> for StudyMode {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        StudyMode::from_str(form_value.as_str()).map_err(|_| form_value)
     }
 }
 
@@ -38,24 +133,95 @@ impl Default for ServerAcceptLangauge {
         ServerAcceptLangauge::English
     }
 }
+impl ServerAcceptLangauge {
+    /// The supported languages, the default first.
+    pub const ALL: [ServerAcceptLangauge; 3] = [
+        ServerAcceptLangauge::English,
+        ServerAcceptLangauge::SimpliedChinese,
+        ServerAcceptLangauge::Japananese,
+    ];
+
+    /// The supported language of a tag like "zh-Hans-CN" or "en_GB", by its primary subtag.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(|c: char| c == '-' || c == '_').next()?;
+        match primary.to_ascii_lowercase().as_str() {
+            "zh" => Some(ServerAcceptLangauge::SimpliedChinese),
+            "ja" => Some(ServerAcceptLangauge::Japananese),
+            "en" => Some(ServerAcceptLangauge::English),
+            _ => None,
+        }
+    }
+
+    /// A language chosen on the site, earlier versions stored japanese as "jp".
+    fn from_choice(value: &str) -> Option<Self> {
+        match value {
+            "jp" => Some(ServerAcceptLangauge::Japananese),
+            _ => ServerAcceptLangauge::from_tag(value),
+        }
+    }
+
+    /// The best supported language of an Accept-Language header (RFC 7231 5.3.5):
+    /// the one of the highest quality, the first of equal ones, "*" is any language not refused by q=0.
+    pub fn negotiate(header: &str) -> Option<Self> {
+        let mut ranges: Vec<(&str, f32)> = header.split(',').filter_map(language_range).collect();
+        let refused: Vec<Self> = ranges
+            .iter()
+            .filter(|(_, quality)| *quality <= 0.0)
+            .filter_map(|(tag, _)| ServerAcceptLangauge::from_tag(tag))
+            .collect();
+        ranges.retain(|(_, quality)| *quality > 0.0);
+        // stable, so equal qualities keep the order of the header
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        ranges.iter().find_map(|(tag, _)| match *tag {
+            "*" => ServerAcceptLangauge::ALL.iter().copied().find(|lang| !refused.contains(lang)),
+            tag => ServerAcceptLangauge::from_tag(tag),
+        })
+    }
+}
+
+/// A language range and its quality, `None` if the quality is malformed.
+fn language_range(range: &str) -> Option<(&str, f32)> {
+    let mut params = range.split(';');
+    let tag = params.next()?.trim();
+    if tag.is_empty() {
+        return None;
+    }
+    let mut quality = 1.0;
+    for param in params {
+        // not sliced by bytes, the header may be any text
+        let mut param = param.splitn(2, '=');
+        if let (Some(name), Some(value)) = (param.next(), param.next()) {
+            if name.trim().eq_ignore_ascii_case("q") {
+                quality = value.trim().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?;
+            }
+        }
+    }
+    Some((tag, quality))
+}
+
 impl<'a, 'r> FromRequest<'a, 'r> for ServerAcceptLangauge {
     type Error = &'r RawStr;
 
+    /// `?lang=` first, then the choice of /api/set-lang, then the browser's Accept-Language.
     fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
-        let first_lang: Option<&str> = request.headers().get("accept-language").next();
-        match first_lang {
-            // TODO process raw string here
-            Some(lang) => {
-                if lang.contains("zh") {
-                    Outcome::Success(ServerAcceptLangauge::SimpliedChinese)
-                } else if lang.contains("jp") {
-                    Outcome::Success(ServerAcceptLangauge::Japananese)
-                } else {
-                    Outcome::Success(ServerAcceptLangauge::English)
-                }
-            }
-            None => Outcome::Success(ServerAcceptLangauge::English),
-        }
+        let query = request
+            .get_query_value::<String>("lang")
+            .and_then(Result::ok)
+            .and_then(|lang| ServerAcceptLangauge::from_choice(&lang));
+        let lang = query
+            .or_else(|| {
+                request
+                    .cookies()
+                    .get("state_choosen_lang")
+                    .and_then(|cookie| ServerAcceptLangauge::from_choice(cookie.value()))
+            })
+            .or_else(|| {
+                request
+                    .headers()
+                    .get("accept-language")
+                    .find_map(ServerAcceptLangauge::negotiate)
+            });
+        Outcome::Success(lang.unwrap_or_default())
     }
 }
 impl<'r> FromParam<'r> for ServerAcceptLangauge {
@@ -68,12 +234,7 @@ impl<'r> FromParam<'r> for ServerAcceptLangauge {
 
 impl From<&str> for ServerAcceptLangauge {
     fn from(s: &str) -> Self {
-        match s {
-            "zh" => (ServerAcceptLangauge::SimpliedChinese),
-            "jp" => (ServerAcceptLangauge::Japananese),
-            "en" => (ServerAcceptLangauge::English),
-            _ => (ServerAcceptLangauge::English),
-        }
+        ServerAcceptLangauge::from_choice(s).unwrap_or_default()
     }
 }
 
@@ -81,7 +242,7 @@ impl fmt::Display for ServerAcceptLangauge {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ServerAcceptLangauge::SimpliedChinese => write!(f, "zh"),
-            ServerAcceptLangauge::Japananese => write!(f, "jp"),
+            ServerAcceptLangauge::Japananese => write!(f, "ja"),
             ServerAcceptLangauge::English => write!(f, "en"),
         }
     }
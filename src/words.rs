@@ -0,0 +1,596 @@
+// Every word of the dictionary a page at a time, in the order chosen with /api/order,
+// searching words by prefix or meaning, a page for each word,
+// and hearts to like words wherever they are shown.
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use rocket::{
+    http::uri::Uri,
+    request::{FlashMessage, Form},
+    response::{Flash, Redirect},
+};
+use rocket_contrib::{
+    databases::diesel::{PgConnection, QueryResult},
+    uuid::Uuid as UuidParam,
+};
+
+use maud::{html, Markup};
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+
+use crate::{app_page_view, books::book_url, db, db::*, flash_text, makrdown_parse_clean, notes, senses_view, TEXT};
+use crate::types::{PanelRankType, ReviewAction, ServerAcceptLangauge};
+
+use anyhow::Result;
+
+pub const PER_PAGE: i64 = 50;
+/// Reviews shown on the page of a word.
+pub const HISTORY_LIMIT: i64 = 20;
+pub const MAX_EXAMPLE_LEN: usize = 2000;
+
+/// A page of words with what is shown next to them.
+struct WordList {
+    words: Vec<Vocab>,
+    /// "partofspeech meaning" of each word on the page
+    meanings: HashMap<String, Vec<String>>,
+    liked: HashSet<String>,
+    page: i64,
+    pages: i64,
+}
+
+impl WordList {
+    fn load(user: &User, (words, pages): (Vec<Vocab>, i64), page: i64, conn: &db::Connection) -> Result<WordList> {
+        let names: Vec<String> = words.iter().map(|w| w.vocab.clone()).collect();
+        let mut meanings: HashMap<String, Vec<String>> = HashMap::new();
+        for sense in VocabDict::get_by_words(&names, conn)? {
+            meanings
+                .entry(sense.vocab)
+                .or_default()
+                .push(format!("{} {}", sense.partofspeech, sense.meaning));
+        }
+        let liked = VocabLike::liked_among(&user.id, &names, conn)?.into_iter().collect();
+        Ok(WordList {
+            words,
+            meanings,
+            liked,
+            page,
+            pages,
+        })
+    }
+}
+
+pub fn word_url(vocab: &str) -> String {
+    format!("/word/{}", Uri::percent_encode(vocab))
+}
+
+fn words_url(page: i64) -> String {
+    format!("/words?page={}", page)
+}
+
+fn search_url(query: &str, page: i64) -> String {
+    format!("/search?q={}&page={}", Uri::percent_encode(query), page)
+}
+
+fn rank_url(rank: PanelRankType) -> String {
+    format!("/api/order?rankby={}", Uri::percent_encode(rank.into()))
+}
+
+fn rank_text_key(rank: PanelRankType) -> &'static str {
+    match rank {
+        PanelRankType::MostRecent => "rank-most-recent",
+        PanelRankType::MostReview => "rank-most-review",
+        PanelRankType::MostLike => "rank-most-like",
+    }
+}
+
+#[get("/words?<page>")]
+pub fn words_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    rank: PanelRankType,
+    page: Option<i64>,
+    conn: db::Connection,
+) -> Result<Markup> {
+    let flash_msg = flash_text(flash);
+    let page = page.unwrap_or(1).max(1);
+    let list = WordList::load(&user, Vocab::get_paginate(rank, page, PER_PAGE, &conn)?, page, &conn)?;
+    Ok(words_view(lang, &user, &flash_msg, rank, &list))
+}
+
+#[get("/search?<q>&<page>")]
+pub fn search_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    q: Option<String>,
+    page: Option<i64>,
+    conn: db::Connection,
+) -> Result<Markup> {
+    let flash_msg = flash_text(flash);
+    let query = q.as_ref().map_or("", |q| q.trim());
+    let page = page.unwrap_or(1).max(1);
+    let list = if query.is_empty() {
+        None
+    } else {
+        Some(WordList::load(&user, Vocab::search(query, page, PER_PAGE, &conn)?, page, &conn)?)
+    };
+    Ok(search_view(lang, &user, &flash_msg, query, list.as_ref()))
+}
+
+fn words_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    rank: PanelRankType,
+    list: &WordList,
+) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            h1 class="title" { (text["words-title"]) }
+            div class="tabs" {
+                ul {
+                    @for each in PanelRankType::iter() {
+                        li class=(if each == rank { "is-active" } else { "" }) {
+                            a href=(rank_url(each)) { (text[rank_text_key(each)]) }
+                        }
+                    }
+                }
+            }
+            (word_table_view(&lang, list, &words_url(list.page)))
+            (pagination_view(&lang, list.page, list.pages, words_url))
+        },
+    )
+}
+
+fn search_view(
+    lang: ServerAcceptLangauge,
+    user: &User,
+    flash_msg: &Option<String>,
+    query: &str,
+    list: Option<&WordList>,
+) -> Markup {
+    let text = &TEXT[&lang];
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            h1 class="title" { (text["search-title"]) }
+            (search_form(&lang, query))
+            @if let Some(list) = list {
+                @if list.words.is_empty() {
+                    p class="has-text-grey" { (text["search-nothing"]) }
+                } @else {
+                    (word_table_view(&lang, list, &search_url(query, list.page)))
+                    (pagination_view(&lang, list.page, list.pages, |page| search_url(query, page)))
+                }
+            }
+        },
+    )
+}
+
+pub fn search_form(lang: &ServerAcceptLangauge, query: &str) -> Markup {
+    html! {
+        form action=(uri!(search_page: _, _).to_string()) method="get" {
+            div class="field has-addons" {
+                div class="control is-expanded" {
+                    input class="input" type="search" name="q" value=(query) placeholder=(TEXT[lang]["search-placeholder"]) {}
+                }
+                div class="control" {
+                    button class="button is-primary" type="submit" { (TEXT[lang]["search-submit"]) }
+                }
+            }
+        }
+    }
+}
+
+/// `back` is the url of the page the table is on.
+fn word_table_view(lang: &ServerAcceptLangauge, list: &WordList, back: &str) -> Markup {
+    let text = &TEXT[lang];
+    html! {
+        table class="table is-fullwidth is-striped" {
+            thead {
+                tr {
+                    th { (text["words-word"]) }
+                    th { (text["words-meaning"]) }
+                    th class="has-text-right" { (text["words-reviews"]) }
+                    th class="has-text-right" { (text["words-likes"]) }
+                    th {}
+                }
+            }
+            tbody {
+                @for word in &list.words {
+                    tr {
+                        td { a href=(word_url(&word.vocab)) { (word.vocab) } }
+                        td {
+                            @if let Some(senses) = list.meanings.get(&word.vocab) {
+                                (senses.join("; "))
+                            }
+                        }
+                        td class="has-text-right" { (word.review_count) }
+                        td class="has-text-right" { (word.like_count) }
+                        td class="has-text-right" {
+                            (like_button(lang, &word.vocab, list.liked.contains(&word.vocab), back))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pagination_view(lang: &ServerAcceptLangauge, page: i64, pages: i64, url: impl Fn(i64) -> String) -> Markup {
+    html! {
+        @if pages > 1 {
+            nav class="pagination is-centered" role="navigation" aria-label="pagination" {
+                @if page > 1 {
+                    a class="pagination-previous" href=(url(page - 1)) { (TEXT[lang]["page-prev"]) }
+                }
+                @if page < pages {
+                    a class="pagination-next" href=(url(page + 1)) { (TEXT[lang]["page-next"]) }
+                }
+                ul class="pagination-list" {
+                    li { span class="pagination-ellipsis" { (page) " / " (pages) } }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct LikeForm {
+    vocab: String,
+    /// the page the button is on
+    back: String,
+}
+
+/// Only paths on this site, never another host.
+pub fn back_url(back: &str) -> String {
+    // browsers take "/\host" like "//host"
+    if back.starts_with('/') && !back.starts_with("//") && !back.starts_with("/\\") {
+        back.to_string()
+    } else {
+        "/".to_string()
+    }
+}
+
+#[post("/likes", data = "<form>")]
+pub fn like_word(
+    lang: ServerAcceptLangauge,
+    user: User,
+    form: Form<LikeForm>,
+    conn: db::Connection,
+) -> Result<Result<Redirect, Flash<Redirect>>> {
+    let back = back_url(&form.back);
+    if !Vocab::exists(&form.vocab, &conn)? {
+        return Ok(Err(Flash::warning(Redirect::to(back), TEXT[&lang]["word-unknown"])));
+    }
+    VocabLike::insert(&user.id, &form.vocab, &conn)?;
+    Ok(Ok(Redirect::to(back)))
+}
+
+#[post("/likes/remove", data = "<form>")]
+pub fn unlike_word(user: User, form: Form<LikeForm>, conn: db::Connection) -> Result<Redirect> {
+    VocabLike::delete(&user.id, &form.vocab, &conn)?;
+    Ok(Redirect::to(back_url(&form.back)))
+}
+
+/// A heart that likes `word`, or unlikes it if `liked`, and comes back to `back`.
+pub fn like_button(lang: &ServerAcceptLangauge, word: &str, liked: bool, back: &str) -> Markup {
+    let (action, title, color) = if liked {
+        (uri!(unlike_word).to_string(), TEXT[lang]["unlike"], "has-text-danger")
+    } else {
+        (uri!(like_word).to_string(), TEXT[lang]["like"], "has-text-grey-light")
+    };
+    html! {
+        form action=(action) method="post" style="display: inline" {
+            input type="hidden" name="vocab" value=(word) {}
+            input type="hidden" name="back" value=(back) {}
+            button class="button is-white is-small" type="submit" title=(title) {
+                span class={"icon " (color)} { i class="fas fa-heart" {} }
+            }
+        }
+    }
+}
+
+pub struct WordPage {
+    pub vocab: Vocab,
+    pub senses: Vec<SenseGroup>,
+    /// each sense with its examples, rendered
+    pub examples: Vec<(VocabDict, Vec<(VocabExample, String)>)>,
+    pub has_speech: bool,
+    pub liked: bool,
+    pub note: Option<notes::Note>,
+    /// the user's books with the word, and the others
+    pub books: (Vec<VocabBook>, Vec<VocabBook>),
+    pub progress: Option<VocabProgress>,
+    pub history: Vec<ReviewLog>,
+}
+
+impl WordPage {
+    /// `None` for a word not in the dictionary.
+    pub fn load(user_id: &Uuid, vocab: &str, conn: &PgConnection) -> Result<Option<WordPage>> {
+        let vocab = match Vocab::get(vocab, conn)? {
+            Some(vocab) => vocab,
+            None => return Ok(None),
+        };
+        let containing = VocabBook::containing(user_id, &vocab.vocab, conn)?;
+        let others = VocabBook::get_by_user(user_id, conn)?
+            .into_iter()
+            .filter(|book| containing.iter().all(|b| b.id != book.id))
+            .collect();
+        let senses = VocabDict::get_by_word(&vocab.vocab, conn)?;
+        let mut examples: Vec<_> = senses.iter().map(|sense| (sense.clone(), vec![])).collect();
+        for example in VocabExample::get_by_word(&vocab.vocab, conn)? {
+            if let Some((_, of_sense)) = examples.iter_mut().find(|(sense, _)| sense.id == example.dict_id) {
+                let html = makrdown_parse_clean(&example.body, Some(&vocab.vocab));
+                of_sense.push((example, html));
+            }
+        }
+        Ok(Some(WordPage {
+            senses: VocabDict::group_senses(&senses),
+            examples,
+            has_speech: VocabSpeech::exists(&vocab.vocab, conn)?,
+            liked: VocabLike::exists(user_id, &vocab.vocab, conn)?,
+            note: notes::Note::load(user_id, &vocab.vocab, conn)?,
+            books: (containing, others),
+            progress: VocabProgress::get(user_id, &vocab.vocab, conn)?,
+            history: ReviewLog::get_by_word(user_id, &vocab.vocab, HISTORY_LIMIT, conn)?,
+            vocab,
+        }))
+    }
+}
+
+#[get("/word/<vocab>")]
+pub fn word_page(
+    lang: ServerAcceptLangauge,
+    user: User,
+    flash: Option<FlashMessage>,
+    vocab: String,
+    conn: db::Connection,
+) -> Result<Option<Markup>> {
+    let flash_msg = flash_text(flash);
+    Ok(WordPage::load(&user.id, &vocab, &conn)?.map(|model| word_view(lang, &user, &flash_msg, &model)))
+}
+
+#[derive(Debug, FromForm)]
+pub struct WordBookForm {
+    book: UuidParam,
+}
+
+#[post("/word/<vocab>/books", data = "<form>")]
+pub fn add_to_book(
+    lang: ServerAcceptLangauge,
+    user: User,
+    vocab: String,
+    form: Form<WordBookForm>,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    if !add_word_to_book(&user.id, &vocab, &form.book, &conn)? {
+        return Ok(None);
+    }
+    Ok(Some(Flash::success(Redirect::to(word_url(&vocab)), TEXT[&lang]["book-word-added"])))
+}
+
+/// Puts `vocab` in the user's book, `false` when there is no such word or the book is not theirs.
+/// Adding it twice is fine.
+pub fn add_word_to_book(user_id: &Uuid, vocab: &str, book_id: &Uuid, conn: &PgConnection) -> QueryResult<bool> {
+    if !Vocab::exists(vocab, conn)? {
+        return Ok(false);
+    }
+    let book = match VocabBook::get_owned(book_id, user_id, conn)? {
+        Some(book) => book,
+        None => return Ok(false),
+    };
+    VocabBookContent::insert(&book.id, vocab, conn)?;
+    Ok(true)
+}
+
+#[derive(Debug, FromForm)]
+pub struct ExampleForm {
+    dict_id: UuidParam,
+    body: String,
+}
+
+#[post("/word/<vocab>/examples", data = "<form>")]
+pub fn add_example(
+    lang: ServerAcceptLangauge,
+    user: User,
+    vocab: String,
+    form: Form<ExampleForm>,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    let sense = match VocabDict::get(&form.dict_id, &conn)? {
+        Some(sense) if sense.vocab == vocab => sense,
+        _ => return Ok(None),
+    };
+    let back = Redirect::to(word_url(&vocab));
+    let body = form.body.trim();
+    if body.is_empty() || body.chars().count() > MAX_EXAMPLE_LEN {
+        return Ok(Some(Flash::warning(back, TEXT[&lang]["example-invalid"])));
+    }
+    let example = NewVocabExample {
+        dict_id: &sense.id,
+        body,
+        created_by: Some(&user.id),
+    };
+    if !VocabExample::insert(&example, &conn)? {
+        return Ok(Some(Flash::warning(back, TEXT[&lang]["example-exists"])));
+    }
+    Ok(Some(Flash::success(back, TEXT[&lang]["example-added"])))
+}
+
+/// Only by whoever wrote the example, or an admin.
+#[post("/examples/<id>/delete")]
+pub fn delete_example(
+    lang: ServerAcceptLangauge,
+    user: User,
+    id: UuidParam,
+    conn: db::Connection,
+) -> Result<Option<Flash<Redirect>>> {
+    let example = match VocabExample::get(&id, &conn)? {
+        Some(example) if user.is_admin || example.created_by == Some(user.id) => example,
+        _ => return Ok(None),
+    };
+    let sense = VocabDict::get(&example.dict_id, &conn)?;
+    VocabExample::delete(&example.id, &conn)?;
+    let back = sense.map_or("/".to_string(), |sense| word_url(&sense.vocab));
+    Ok(Some(Flash::success(Redirect::to(back), TEXT[&lang]["example-deleted"])))
+}
+
+fn action_text_key(action: &str) -> Option<&'static str> {
+    match ReviewAction::from_str(action).ok()? {
+        ReviewAction::Know => Some("action-know"),
+        ReviewAction::DontKnow => Some("action-dontknow"),
+        ReviewAction::Right => Some("action-right"),
+        ReviewAction::Wrong => Some("action-wrong"),
+    }
+}
+
+fn word_view(lang: ServerAcceptLangauge, user: &User, flash_msg: &Option<String>, model: &WordPage) -> Markup {
+    let text = &TEXT[&lang];
+    let word = &model.vocab.vocab;
+    let url = word_url(word);
+    let (containing, others) = &model.books;
+    app_page_view(
+        &lang,
+        user,
+        flash_msg,
+        html! {
+            div class="level" {
+                div class="level-left" {
+                    h1 class="level-item title is-1" { (word) }
+                    @if model.has_speech {
+                        audio class="level-item" controls? preload="none"
+                              src=(uri!(crate::get_prounciation: word).to_string()) {}
+                    }
+                }
+                div class="level-right" {
+                    span class="level-item" { (like_button(&lang, word, model.liked, &url)) }
+                }
+            }
+            div class="box" {
+                (senses_view(&model.senses, true))
+            }
+            div class="box" {
+                h2 class="subtitle" { (text["note-title"]) }
+                @if let Some(note) = &model.note {
+                    div class="content" { (maud::PreEscaped(&note.html)) }
+                }
+                (notes::note_form(&lang, word, model.note.as_ref(), &url))
+            }
+            div class="box" {
+                h2 class="subtitle" { (text["word-examples"]) }
+                @for (sense, examples) in &model.examples {
+                    @if !examples.is_empty() {
+                        p class="has-text-grey" { (sense.partofspeech) " " (sense.meaning) }
+                        @for (example, html) in examples {
+                            div class="media" {
+                                div class="media-content" {
+                                    div class="content" { (maud::PreEscaped(html)) }
+                                }
+                                @if user.is_admin || example.created_by == Some(user.id) {
+                                    div class="media-right" {
+                                        form action={"/examples/" (example.id) "/delete"} method="post" {
+                                            button class="delete" type="submit" title=(text["example-delete"]) {}
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                @if !model.examples.is_empty() {
+                    form action={(url) "/examples"} method="post" {
+                        div class="field" {
+                            div class="select is-fullwidth" {
+                                select name="dict_id" {
+                                    @for (sense, _) in &model.examples {
+                                        option value=(sense.id) { (sense.partofspeech) " " (sense.meaning) }
+                                    }
+                                }
+                            }
+                        }
+                        div class="field" {
+                            textarea class="textarea" name="body" rows="3" maxlength=(MAX_EXAMPLE_LEN)
+                                     placeholder=(text["example-placeholder"]) required? {}
+                        }
+                        button class="button is-primary" type="submit" { (text["example-add"]) }
+                    }
+                }
+            }
+            div class="columns" {
+                div class="column" {
+                    div class="box" {
+                        h2 class="subtitle" { (text["word-books"]) }
+                        div class="tags" {
+                            @for book in containing {
+                                a class="tag is-info is-light" href=(book_url(&book.id)) { (book.name) }
+                            }
+                        }
+                        @if containing.is_empty() {
+                            p class="has-text-grey" { (text["word-in-no-book"]) }
+                        }
+                        @if !others.is_empty() {
+                            form action={(url) "/books"} method="post" {
+                                div class="field has-addons" {
+                                    div class="control is-expanded" {
+                                        div class="select is-fullwidth" {
+                                            select name="book" {
+                                                @for book in others {
+                                                    option value=(book.id) { (book.name) }
+                                                }
+                                            }
+                                        }
+                                    }
+                                    div class="control" {
+                                        button class="button is-primary" type="submit" { (text["book-add-word"]) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                div class="column" {
+                    div class="box" {
+                        h2 class="subtitle" { (text["word-history"]) }
+                        @match &model.progress {
+                            Some(progress) => {
+                                p {
+                                    (text["word-due"]) " " (progress.due_at.format("%Y-%m-%d"))
+                                    ", " (text["word-interval"]) " " (progress.interval_days) " " (text["stats-days"])
+                                    ", " (text["word-lapses"]) " " (progress.lapses)
+                                }
+                            }
+                            None => { p class="has-text-grey" { (text["word-never-studied"]) } }
+                        }
+                        table class="table is-fullwidth is-narrow" {
+                            tbody {
+                                @for log in &model.history {
+                                    tr {
+                                        td { (log.created_at.format("%Y-%m-%d %H:%M")) }
+                                        td { (action_text_key(&log.action).map_or(log.action.as_str(), |key| text[key])) }
+                                        td class="has-text-right" {
+                                            @match log.passed {
+                                                Some(true) => { span class="tag is-success" { (text["word-passed"]) } }
+                                                Some(false) => { span class="tag is-danger" { (text["word-failed"]) } }
+                                                None => {}
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}